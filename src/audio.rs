@@ -0,0 +1,234 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rg3d::core::pool::Handle;
+use rg3d::physics::na::Vector3;
+use rg3d::sound::context::Context;
+use rg3d::sound::effects::reverb::Reverb;
+use rg3d::sound::effects::{BaseEffect, Effect};
+use rg3d::sound::source::{SoundSource, Status};
+
+use crate::occlusion::{Occlusion, OPEN_CUTOFF};
+use crate::reverb::ReverbParameters;
+use crate::settings::{AudioSettings, BusSettings};
+
+/// every sound plays on one of these, so its volume can be set separately. All of them go
+/// through the master volume as well.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bus {
+    /// the humming of the station, machines and other looping background noise
+    Ambient,
+    /// footsteps and everything else the player does
+    Sfx,
+    /// breathing, groaning and anything else that comes out of a mouth
+    Voice,
+}
+
+struct ManagedSource {
+    handle: Handle<SoundSource>,
+    bus: Bus,
+    /// gain of the source itself, before the volume of the buses is applied
+    gain: f32,
+    /// `gain` moves towards this
+    target_gain: f32,
+    /// how fast `gain` moves, per second
+    fade_speed: f32,
+    /// removed as soon as it faded out completely
+    remove_when_silent: bool,
+    /// sounds that don't loop are removed once they stopped playing
+    looping: bool,
+    /// where spatial sources are, for the occlusion
+    position: Option<Vector3<f32>>,
+    /// how much of the sound gets through the walls, from 0 to 1
+    occlusion: f32,
+    /// `occlusion` moves towards this
+    target_occlusion: f32,
+    /// cutoff of the low-pass the walls put on the sound, in Hz. The sound context has no
+    /// filters for single sources yet, only effects shared by all of them like the reverb, so
+    /// it's kept up to date to be applied next to the gain once it does.
+    cutoff: f32,
+    /// `cutoff` moves towards this
+    target_cutoff: f32,
+}
+
+/// owns all sound sources of the game. They are added to the sound context through it and it
+/// takes care of their volume, fades and removal. Every source goes through the reverb.
+pub struct AudioManager {
+    ctx: Arc<Mutex<Context>>,
+    sources: Vec<ManagedSource>,
+    reverb: Handle<Effect>,
+    /// volumes of the buses, as of the last `update`
+    settings: AudioSettings,
+}
+
+impl AudioManager {
+    /// how fast the occlusion of a source changes, per second
+    const OCCLUSION_SPEED: f32 = 2.0;
+
+    pub fn new(ctx: Arc<Mutex<Context>>, settings: &AudioSettings) -> Self {
+        let reverb = ctx
+            .lock()
+            .unwrap()
+            .add_effect(Effect::Reverb(Reverb::new(BaseEffect::default())));
+
+        let mut audio = Self {
+            ctx,
+            sources: Vec::new(),
+            reverb,
+            settings: settings.clone(),
+        };
+        audio.set_reverb(ReverbParameters::DEFAULT);
+        audio
+    }
+
+    /// starts the source on the bus. The gain it was built with is its gain before the volume of
+    /// the bus is applied. Sources that don't loop are removed when they're done, their handle
+    /// isn't valid after that.
+    pub fn play(&mut self, source: SoundSource, bus: Bus) -> Handle<SoundSource> {
+        let gain = source.gain();
+        let looping = source.is_looping();
+        let position = match &source {
+            SoundSource::Spatial(spatial) => Some(spatial.position()),
+            SoundSource::Generic(_) => None,
+        };
+
+        let mut source = source;
+        source.set_gain(gain * Self::volume(&self.settings, bus));
+        let mut ctx = self.ctx.lock().unwrap();
+        let handle = ctx.add_source(source);
+        ctx.effect_mut(self.reverb).add_input(handle);
+        drop(ctx);
+
+        self.sources.push(ManagedSource {
+            handle,
+            bus,
+            gain,
+            target_gain: gain,
+            fade_speed: 0.0,
+            remove_when_silent: false,
+            looping,
+            position,
+            occlusion: 1.0,
+            target_occlusion: 1.0,
+            cutoff: OPEN_CUTOFF,
+            target_cutoff: OPEN_CUTOFF,
+        });
+        handle
+    }
+
+    /// like `play`, but fades the sound in from silence.
+    pub fn play_faded(
+        &mut self,
+        source: SoundSource,
+        bus: Bus,
+        seconds: f32,
+    ) -> Handle<SoundSource> {
+        let handle = self.play(source, bus);
+        if let Some(source) = self.find(handle) {
+            let gain = source.gain;
+            source.gain = 0.0;
+            source.target_gain = gain;
+            source.fade_speed = gain / seconds.max(f32::EPSILON);
+        }
+        self.ctx.lock().unwrap().source_mut(handle).set_gain(0.0);
+        handle
+    }
+
+    /// moves the gain of the source to `gain` over the given time. Calling it again with the same
+    /// gain doesn't restart the fade.
+    pub fn fade_to(&mut self, handle: Handle<SoundSource>, gain: f32, seconds: f32) {
+        if let Some(source) = self.find(handle) {
+            if source.target_gain != gain {
+                source.target_gain = gain;
+                source.fade_speed = (gain - source.gain).abs() / seconds.max(f32::EPSILON);
+            }
+        }
+    }
+
+    /// fades the source out and removes it afterwards.
+    pub fn fade_out(&mut self, handle: Handle<SoundSource>, seconds: f32) {
+        self.fade_to(handle, 0.0, seconds);
+        if let Some(source) = self.find(handle) {
+            source.remove_when_silent = true;
+        }
+    }
+
+    /// handles and positions of all spatial sources
+    pub fn spatial_sources(&self) -> Vec<(Handle<SoundSource>, Vector3<f32>)> {
+        self.sources
+            .iter()
+            .filter_map(|s| s.position.map(|position| (s.handle, position)))
+            .collect()
+    }
+
+    /// what the walls do to the source, see `SoundOcclusion`. It changes smoothly.
+    pub fn set_occlusion(&mut self, handle: Handle<SoundSource>, occlusion: Occlusion) {
+        if let Some(source) = self.find(handle) {
+            source.target_occlusion = occlusion.gain;
+            source.target_cutoff = occlusion.cutoff;
+        }
+    }
+
+    /// advances the fades, applies the volumes of the buses and removes the sources that are
+    /// done. Must be called every tick.
+    pub fn update(&mut self, settings: &AudioSettings, dt: f32) {
+        self.settings = settings.clone();
+
+        let ctx = self.ctx.clone();
+        let mut ctx = ctx.lock().unwrap();
+        let reverb = self.reverb;
+
+        self.sources.retain(|source| {
+            let done = (source.remove_when_silent && source.gain == 0.0)
+                || (!source.looping && ctx.source(source.handle).status() == Status::Stopped);
+            if done {
+                ctx.effect_mut(reverb).remove_input(source.handle);
+                ctx.remove_source(source.handle);
+            }
+            !done
+        });
+
+        for source in &mut self.sources {
+            let step = source.fade_speed * dt;
+            source.gain += (source.target_gain - source.gain).max(-step).min(step);
+
+            let step = Self::OCCLUSION_SPEED * dt;
+            source.occlusion += (source.target_occlusion - source.occlusion)
+                .max(-step)
+                .min(step);
+            // in octaves, so muffling fades in as fast as it fades out
+            let octaves = (source.target_cutoff / source.cutoff).log2();
+            source.cutoff *= octaves.max(-step).min(step).exp2();
+
+            let volume = Self::volume(&self.settings, source.bus);
+            ctx.source_mut(source.handle)
+                .set_gain(source.gain * source.occlusion * volume);
+        }
+    }
+
+    /// changes the echo of all sounds, see `RoomReverb` for blending between rooms.
+    pub fn set_reverb(&mut self, parameters: ReverbParameters) {
+        match self.ctx.lock().unwrap().effect_mut(self.reverb) {
+            Effect::Reverb(reverb) => {
+                reverb.set_decay_time(Duration::from_secs_f32(parameters.decay_time.max(0.01)));
+                reverb.set_wet(parameters.wet);
+                reverb.set_dry(1.0 - parameters.wet);
+            }
+        }
+    }
+
+    /// volume of the bus including the master volume
+    fn volume(settings: &AudioSettings, bus: Bus) -> f32 {
+        let volume = |bus: &BusSettings| if bus.muted { 0.0 } else { bus.volume };
+        let bus = match bus {
+            Bus::Ambient => &settings.ambient,
+            Bus::Sfx => &settings.sfx,
+            Bus::Voice => &settings.voice,
+        };
+        volume(bus) * volume(&settings.master)
+    }
+
+    fn find(&mut self, handle: Handle<SoundSource>) -> Option<&mut ManagedSource> {
+        self.sources.iter_mut().find(|s| s.handle == handle)
+    }
+}
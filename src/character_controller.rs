@@ -0,0 +1,225 @@
+use rg3d::core::math::ray::Ray;
+use rg3d::core::pool::Handle;
+use rg3d::physics::dynamics::{RigidBodyBuilder, RigidBodyHandle};
+use rg3d::physics::geometry::{Collider, ColliderBuilder, ColliderHandle, InteractionGroups};
+use rg3d::physics::na::Vector3;
+use rg3d::scene::base::BaseBuilder;
+use rg3d::scene::node::Node;
+use rg3d::scene::physics::RayCastOptions;
+use rg3d::scene::Scene;
+
+use crate::player::Player;
+
+/// a capsule shaped rigid body that carries the camera around. Gravity, jumping and the walls
+/// are handled by the physics engine, the player only decides where to go.
+pub struct CharacterController {
+    body: RigidBodyHandle,
+    collider: ColliderHandle,
+    /// bound to the body, the camera is attached to it
+    pivot: Handle<Node>,
+    crouching: bool,
+    on_ground: bool,
+}
+
+impl CharacterController {
+    /// half the height of the cylindrical part of the capsule
+    const STAND_HALF_HEIGHT: f32 = 0.15;
+    const CROUCH_HALF_HEIGHT: f32 = 0.02;
+    /// vertical velocity change of a jump in units per second
+    const JUMP_SPEED: f32 = 2.2;
+    /// how far below the capsule the ground may be to still count as standing on it
+    const GROUND_TOLERANCE: f32 = 0.05;
+
+    /// creates the body standing on the floor at `position`.
+    pub fn new(scene: &mut Scene, position: Vector3<f32>) -> Self {
+        let pivot = scene.graph.add_node(Node::Base(BaseBuilder::new().build()));
+
+        let center = position.y + Self::STAND_HALF_HEIGHT + Player::RADIUS;
+
+        let body = scene.physics.bodies.insert(
+            RigidBodyBuilder::new_dynamic()
+                .lock_rotations()
+                .translation(position.x, center, position.z)
+                .build(),
+        );
+
+        let collider = scene.physics.colliders.insert(
+            Self::capsule(Self::STAND_HALF_HEIGHT),
+            body,
+            &mut scene.physics.bodies,
+        );
+
+        scene.physics_binder.bind(pivot, body);
+
+        // the binder only syncs after the first physics step
+        scene.graph[pivot]
+            .local_transform_mut()
+            .set_position(Vector3::new(position.x, center, position.z));
+
+        Self {
+            body,
+            collider,
+            pivot,
+            crouching: false,
+            on_ground: false,
+        }
+    }
+
+    fn capsule(half_height: f32) -> Collider {
+        ColliderBuilder::capsule_y(half_height, Player::RADIUS)
+            // otherwise the capsule sticks to walls while sliding along them
+            .friction(0.0)
+            .build()
+    }
+
+    pub fn pivot(&self) -> Handle<Node> {
+        self.pivot
+    }
+
+    /// changes when crouching, don't keep it around
+    pub fn collider(&self) -> ColliderHandle {
+        self.collider
+    }
+
+    pub fn is_on_ground(&self) -> bool {
+        self.on_ground
+    }
+
+    /// position of the center of the capsule
+    pub fn position(&self, scene: &Scene) -> Vector3<f32> {
+        scene.physics.bodies[self.body]
+            .position()
+            .translation
+            .vector
+    }
+
+    /// height of the eyes above the center of the capsule, they sit where the upper half
+    /// sphere begins
+    pub fn eye_height(&self) -> f32 {
+        self.half_height()
+    }
+
+    fn half_height(&self) -> f32 {
+        if self.crouching {
+            Self::CROUCH_HALF_HEIGHT
+        } else {
+            Self::STAND_HALF_HEIGHT
+        }
+    }
+
+    /// moves horizontally with `velocity` (units per second), jumps if requested and possible
+    /// and switches between standing and crouching. Must be called once per physics step.
+    pub fn update(&mut self, scene: &mut Scene, velocity: (f32, f32), jump: bool, crouch: bool) {
+        self.on_ground = self.check_ground(scene);
+
+        if crouch != self.crouching {
+            self.set_crouching(scene, crouch);
+        }
+
+        let body = &mut scene.physics.bodies[self.body];
+
+        let mut linvel = *body.linvel();
+        linvel.x = velocity.0;
+        linvel.z = velocity.1;
+
+        if jump && self.on_ground && !self.crouching {
+            linvel.y += Self::JUMP_SPEED;
+            self.on_ground = false;
+        }
+
+        body.set_linvel(linvel, true);
+    }
+
+    /// swaps the collider for a shorter or taller one. The bottom of the capsule stays where
+    /// it is, so crouching doesn't drop the player and standing up doesn't push them into the
+    /// floor.
+    fn set_crouching(&mut self, scene: &mut Scene, crouching: bool) {
+        let old_half_height = self.half_height();
+        self.crouching = crouching;
+        let new_half_height = self.half_height();
+
+        scene
+            .physics
+            .colliders
+            .remove(self.collider, &mut scene.physics.bodies, true);
+
+        self.collider = scene.physics.colliders.insert(
+            Self::capsule(new_half_height),
+            self.body,
+            &mut scene.physics.bodies,
+        );
+
+        let body = &mut scene.physics.bodies[self.body];
+        let mut position = *body.position();
+        position.translation.vector.y += new_half_height - old_half_height;
+        body.set_position(position, true);
+    }
+
+    /// casts a short ray down from the bottom of the capsule.
+    fn check_ground(&self, scene: &mut Scene) -> bool {
+        let center = self.position(scene);
+        let bottom = self.half_height() + Player::RADIUS;
+
+        let mut intersections = Vec::new();
+        scene.physics.cast_ray(
+            RayCastOptions {
+                ray: Ray::new(center, Vector3::new(0.0, -1.0, 0.0)),
+                max_len: bottom + Self::GROUND_TOLERANCE,
+                groups: InteractionGroups::all(),
+                sort_results: false,
+            },
+            &mut intersections,
+        );
+
+        intersections.iter().any(|i| i.collider != self.collider)
+    }
+}
+
+/// creates a static body with colliders for the floor and all solid tiles next to walkable ones.
+/// `solid` and `width`/`height` describe the tile grid, `origin` is where tile `(0, 0)` is.
+pub fn add_level_colliders(
+    scene: &mut Scene,
+    origin: Vector3<f32>,
+    width: usize,
+    height: usize,
+    solid: impl Fn(i32, i32) -> bool,
+) -> RigidBodyHandle {
+    let body = scene.physics.bodies.insert(
+        RigidBodyBuilder::new_static()
+            .translation(origin.x, origin.y, origin.z)
+            .build(),
+    );
+
+    // one slab for the whole floor, its top is at y = 0
+    scene.physics.colliders.insert(
+        ColliderBuilder::cuboid(width as f32 / 2.0, 0.1, height as f32 / 2.0)
+            .translation(width as f32 / 2.0 - 0.5, -0.1, height as f32 / 2.0 - 0.5)
+            .build(),
+        body,
+        &mut scene.physics.bodies,
+    );
+
+    for x in 0..width as i32 {
+        for y in 0..height as i32 {
+            if !solid(x, y) {
+                continue;
+            }
+
+            let borders_walkable_tile = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                .iter()
+                .any(|&(n_x, n_y)| !solid(n_x, n_y));
+
+            if borders_walkable_tile {
+                scene.physics.colliders.insert(
+                    ColliderBuilder::cuboid(0.5, 1.0, 0.5)
+                        .translation(x as f32, 1.0, y as f32)
+                        .build(),
+                    body,
+                    &mut scene.physics.bodies,
+                );
+            }
+        }
+    }
+
+    body
+}
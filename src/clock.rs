@@ -0,0 +1,16 @@
+/// simulation time in seconds. It only moves forward when the simulation does, so everything
+/// timed with it behaves the same at any tick rate and can be driven by hand.
+#[derive(Default, Copy, Clone)]
+pub struct GameClock {
+    elapsed: f32,
+}
+
+impl GameClock {
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    pub fn now(&self) -> f32 {
+        self.elapsed
+    }
+}
@@ -0,0 +1,252 @@
+use crate::level_generator::{FieldType, Level};
+
+/// a grid of tiles, one unit wide and centered on their coordinates, that are either walkable
+/// or solid.
+pub trait CollisionGrid {
+    fn is_solid(&self, tile: (i32, i32)) -> bool;
+}
+
+impl CollisionGrid for Level {
+    /// the walls are placed on the edges of empty tiles, so empty tiles are the solid ones.
+    /// Everything outside of the map is solid, too.
+    fn is_solid(&self, (x, y): (i32, i32)) -> bool {
+        if x < 0 || y < 0 || x as usize >= self.width() || y as usize >= self.height() {
+            return true;
+        }
+
+        self.map[x as usize][y as usize].typ == FieldType::Empty
+    }
+}
+
+/// how many times the position is pushed out of the walls per step. Two walls can push against
+/// each other in inner corners, so once isn't always enough.
+const ITERATIONS: usize = 3;
+
+/// moves a circle with the given radius by `movement`, without letting it enter solid tiles.
+/// The part of the movement going into a wall is dropped and the rest is kept, so the circle
+/// slides along walls instead of stopping dead.
+pub fn move_and_slide(
+    grid: &impl CollisionGrid,
+    position: (f32, f32),
+    movement: (f32, f32),
+    radius: f32,
+) -> (f32, f32) {
+    // small steps, so a fast movement can't tunnel through a wall
+    let length = movement.0.hypot(movement.1);
+    let steps = (length / (radius * 0.5)).ceil().max(1.0) as usize;
+
+    let mut position = position;
+    for _ in 0..steps {
+        position.0 += movement.0 / steps as f32;
+        position.1 += movement.1 / steps as f32;
+
+        for _ in 0..ITERATIONS {
+            if !push_out(grid, &mut position, radius) {
+                break;
+            }
+        }
+    }
+
+    position
+}
+
+/// pushes the circle out of all solid tiles it overlaps with. Returns whether it had to.
+fn push_out(grid: &impl CollisionGrid, position: &mut (f32, f32), radius: f32) -> bool {
+    let center = (position.0.round() as i32, position.1.round() as i32);
+
+    let mut pushed = false;
+
+    for x in (center.0 - 1)..=(center.0 + 1) {
+        for y in (center.1 - 1)..=(center.1 + 1) {
+            if !grid.is_solid((x, y)) {
+                continue;
+            }
+
+            // closest point of the tile to the center of the circle
+            let closest = (
+                position.0.max(x as f32 - 0.5).min(x as f32 + 0.5),
+                position.1.max(y as f32 - 0.5).min(y as f32 + 0.5),
+            );
+
+            let delta = (position.0 - closest.0, position.1 - closest.1);
+            let distance = delta.0.hypot(delta.1);
+
+            if distance >= radius {
+                continue;
+            }
+
+            if distance > f32::EPSILON {
+                let depth = radius - distance;
+                position.0 += delta.0 / distance * depth;
+                position.1 += delta.1 / distance * depth;
+            } else {
+                // the center is inside the tile, leave along the shortest way
+                let left = position.0 - (x as f32 - 0.5);
+                let right = (x as f32 + 0.5) - position.0;
+                let up = position.1 - (y as f32 - 0.5);
+                let down = (y as f32 + 0.5) - position.1;
+
+                let shortest = left.min(right).min(up).min(down);
+                if shortest == left {
+                    position.0 -= left + radius;
+                } else if shortest == right {
+                    position.0 += right + radius;
+                } else if shortest == up {
+                    position.1 -= up + radius;
+                } else {
+                    position.1 += down + radius;
+                }
+            }
+
+            pushed = true;
+        }
+    }
+
+    pushed
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// a level with a closed door at `door`, like `World` does it
+    pub(crate) struct ClosedDoor {
+        pub level: Level,
+        pub door: (i32, i32),
+    }
+
+    impl CollisionGrid for ClosedDoor {
+        fn is_solid(&self, tile: (i32, i32)) -> bool {
+            tile == self.door || self.level.is_solid(tile)
+        }
+    }
+
+    /// same as `Player::RADIUS`
+    const RADIUS: f32 = 0.2;
+
+    /// two rooms above each other, connected by a door at (3, 3)
+    pub(crate) fn two_rooms() -> Level {
+        Level::parse(
+            "#######
+             #.....#
+             #.....#
+             ###+###
+             #.....#
+             #.....#
+             #######",
+        )
+    }
+
+    /// whether the circle overlaps a solid tile, with a little tolerance
+    fn overlaps_wall(grid: &impl CollisionGrid, (x, y): (f32, f32)) -> bool {
+        let center = (x.round() as i32, y.round() as i32);
+        (center.0 - 1..=center.0 + 1)
+            .flat_map(|tx| (center.1 - 1..=center.1 + 1).map(move |ty| (tx, ty)))
+            .filter(|&tile| grid.is_solid(tile))
+            .any(|(tx, ty)| {
+                let closest = (
+                    x.max(tx as f32 - 0.5).min(tx as f32 + 0.5),
+                    y.max(ty as f32 - 0.5).min(ty as f32 + 0.5),
+                );
+                (x - closest.0).hypot(y - closest.1) < RADIUS - 0.001
+            })
+    }
+
+    #[test]
+    fn walks_freely_in_the_open() {
+        let level = two_rooms();
+
+        let (x, y) = move_and_slide(&level, (2.0, 1.5), (1.0, 0.0), RADIUS);
+        assert!((x - 3.0).abs() < 0.001 && (y - 1.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let level = two_rooms();
+
+        // diagonally into the wall at the top, the part along it is kept
+        let (x, y) = move_and_slide(&level, (2.0, 1.0), (0.5, -0.5), RADIUS);
+        assert!((x - 2.5).abs() < 0.01, "x is {}", x);
+        assert!((y - (0.5 + RADIUS)).abs() < 0.01, "y is {}", y);
+        assert!(!overlaps_wall(&level, (x, y)));
+    }
+
+    #[test]
+    fn stops_at_an_outer_corner_hit_head_on() {
+        let level = Level::parse(
+            "#####
+             #...#
+             #.#.#
+             #...#
+             #####",
+        );
+
+        // straight at the corner of the pillar at (2, 2)
+        let (x, y) = move_and_slide(&level, (1.0, 1.0), (0.6, 0.6), RADIUS);
+        assert!(!overlaps_wall(&level, (x, y)));
+        assert!((x - y).abs() < 0.01, "pushed to one side: {:?}", (x, y));
+        assert!(x > 1.2, "stopped too early: {:?}", (x, y));
+    }
+
+    #[test]
+    fn slides_around_an_outer_corner() {
+        let level = Level::parse(
+            "#####
+             #...#
+             #.#.#
+             #...#
+             #####",
+        );
+
+        // just off the corner, the wall deflects the circle past it
+        let (x, y) = move_and_slide(&level, (1.0, 1.2), (0.0, 2.0), RADIUS);
+        assert!(!overlaps_wall(&level, (x, y)));
+        assert!(y > 2.5, "got stuck at {:?}", (x, y));
+        assert!(x <= 1.5 - RADIUS + 0.01);
+    }
+
+    #[test]
+    fn squeezes_through_a_door_frame() {
+        let level = two_rooms();
+
+        // slightly off the middle of the doorway, the frame pushes the circle into it
+        let mut position = (3.4, 1.5);
+        for _ in 0..40 {
+            position = move_and_slide(&level, position, (0.0, 0.1), RADIUS);
+            assert!(
+                !overlaps_wall(&level, position),
+                "in the wall at {:?}",
+                position
+            );
+        }
+        assert!(
+            position.1 > 4.5,
+            "stuck in the door frame at {:?}",
+            position
+        );
+    }
+
+    #[test]
+    fn closed_door_blocks_the_way() {
+        let grid = ClosedDoor {
+            level: two_rooms(),
+            door: (3, 3),
+        };
+
+        let (x, y) = move_and_slide(&grid, (3.0, 1.5), (0.0, 3.0), RADIUS);
+        assert!((x - 3.0).abs() < 0.001);
+        assert!((y - (2.5 - RADIUS)).abs() < 0.01, "y is {}", y);
+
+        // once it's open, the same way is free
+        let (_, y) = move_and_slide(&two_rooms(), (3.0, 1.5), (0.0, 3.0), RADIUS);
+        assert!((y - 4.5).abs() < 0.001);
+    }
+
+    #[test]
+    fn fast_movement_does_not_tunnel_through_walls() {
+        let level = two_rooms();
+
+        let (_, y) = move_and_slide(&level, (1.0, 1.5), (0.0, 10.0), RADIUS);
+        assert!(y < 2.5, "went through the wall to {}", y);
+    }
+}
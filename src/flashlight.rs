@@ -0,0 +1,124 @@
+use rand::{thread_rng, Rng};
+use rg3d::core::color::Color;
+use rg3d::core::pool::Handle;
+use rg3d::scene::light::Light;
+use rg3d::scene::node::Node;
+use rg3d::scene::Scene;
+use serde::Deserialize;
+
+/// per level settings for the flashlight, see `assets/level.ron`
+#[derive(Deserialize, Clone)]
+pub struct FlashlightOptions {
+    /// charge used per second while the flashlight is on, a full battery is 1
+    pub drain_rate: f32,
+    /// how many batteries are placed in a chunk, inclusive
+    pub batteries: (usize, usize),
+}
+
+/// the flashlight in the hand of the player. It runs on a battery that can be swapped or
+/// recharged with the crank, and gets dimmer and starts to flicker when it runs low.
+pub struct Flashlight {
+    node: Handle<Node>,
+    on: bool,
+    /// from 0 to 1
+    charge: f32,
+    /// seconds until the flicker toggles again
+    flicker_timer: f32,
+    /// whether a flicker turned the light off at the moment
+    flickered_off: bool,
+}
+
+impl Flashlight {
+    pub const COLOR: (u8, u8, u8) = (232, 226, 185);
+    /// how far the light reaches with a full battery
+    pub const DISTANCE: f32 = 6.0;
+
+    /// below this charge the light gets dimmer
+    const DIM_BELOW: f32 = 0.4;
+    /// below this charge the light flickers
+    const FLICKER_BELOW: f32 = 0.15;
+    /// a dimmed light still has this fraction of its brightness left when the battery is empty
+    const MIN_BRIGHTNESS: f32 = 0.25;
+    /// charge per second of cranking
+    const CRANK_RATE: f32 = 0.04;
+
+    /// `node` is the spot light, it starts switched off.
+    pub fn new(node: Handle<Node>) -> Self {
+        Self {
+            node,
+            on: false,
+            charge: 1.0,
+            flicker_timer: 0.0,
+            flickered_off: false,
+        }
+    }
+
+    /// switches the light on or off. An empty flashlight can't be switched on.
+    pub fn toggle(&mut self) {
+        self.on = !self.on && self.charge > 0.0;
+    }
+
+    /// from 0 to 1, for the HUD
+    pub fn charge(&self) -> f32 {
+        self.charge
+    }
+
+    /// swaps the battery for a full one.
+    pub fn insert_battery(&mut self) {
+        self.charge = 1.0;
+    }
+
+    /// charges the battery a little. Must be called every tick while the crank is turned.
+    pub fn crank(&mut self, dt: f32) {
+        self.charge = (self.charge + Self::CRANK_RATE * dt).min(1.0);
+    }
+
+    pub fn update(&mut self, scene: &mut Scene, dt: f32, options: &FlashlightOptions) {
+        if self.on {
+            self.charge = (self.charge - options.drain_rate * dt).max(0.0);
+            if self.charge == 0.0 {
+                self.on = false;
+            }
+        }
+
+        self.update_flicker(dt);
+
+        let brightness = Self::MIN_BRIGHTNESS
+            + (1.0 - Self::MIN_BRIGHTNESS) * (self.charge / Self::DIM_BELOW).min(1.0);
+
+        let node = &mut scene.graph[self.node];
+        node.set_visibility(self.on && !self.flickered_off);
+
+        if let Node::Light(Light::Spot(spot)) = node {
+            let (r, g, b) = Self::COLOR;
+            spot.set_color(Color::opaque(
+                (r as f32 * brightness) as u8,
+                (g as f32 * brightness) as u8,
+                (b as f32 * brightness) as u8,
+            ));
+            spot.set_distance(Self::DISTANCE * brightness);
+        }
+    }
+
+    /// the lower the charge, the longer and more frequent the blackouts.
+    fn update_flicker(&mut self, dt: f32) {
+        if self.charge >= Self::FLICKER_BELOW {
+            self.flickered_off = false;
+            return;
+        }
+
+        self.flicker_timer -= dt;
+        if self.flicker_timer > 0.0 {
+            return;
+        }
+
+        let weakness = 1.0 - self.charge / Self::FLICKER_BELOW;
+        let mut rng = thread_rng();
+        self.flickered_off = !self.flickered_off;
+        self.flicker_timer = if self.flickered_off {
+            rng.gen_range(0.02, 0.05 + 0.3 * weakness)
+        } else {
+            rng.gen_range(0.1, 2.0 - 1.5 * weakness)
+        };
+    }
+}
@@ -0,0 +1,129 @@
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+use rg3d::engine::resource_manager::{ResourceManager, SharedSoundBuffer};
+use rg3d::sound::source::generic::GenericSourceBuilder;
+use rg3d::sound::source::Status;
+use serde::Deserialize;
+
+use crate::audio::{AudioManager, Bus};
+use crate::level_generator::FieldType;
+use crate::player::PlayerState;
+use crate::ron_file::load_ron;
+
+/// what the player is walking on, every surface sounds different
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Surface {
+    /// the floor of rooms
+    Floor,
+    /// the metal grating of corridors
+    Grating,
+    /// the threshold of doors
+    Threshold,
+}
+
+impl Surface {
+    pub fn of(field: FieldType) -> Self {
+        match field {
+            FieldType::Corridor => Surface::Grating,
+            FieldType::Door => Surface::Threshold,
+            FieldType::Floor | FieldType::Empty => Surface::Floor,
+        }
+    }
+}
+
+/// paths of the sounds of one surface, see `assets/footsteps.ron`
+#[derive(Deserialize)]
+struct SurfaceDefinition {
+    walk: Vec<String>,
+    run: Vec<String>,
+    crouch: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct FootstepDefinitions {
+    floor: SurfaceDefinition,
+    grating: SurfaceDefinition,
+    threshold: SurfaceDefinition,
+}
+
+struct SurfaceSounds {
+    walk: Vec<SharedSoundBuffer>,
+    run: Vec<SharedSoundBuffer>,
+    crouch: Vec<SharedSoundBuffer>,
+}
+
+impl SurfaceSounds {
+    async fn load(resource_manager: &ResourceManager, definition: &SurfaceDefinition) -> Self {
+        Self {
+            walk: load_buffers(resource_manager, &definition.walk).await,
+            run: load_buffers(resource_manager, &definition.run).await,
+            crouch: load_buffers(resource_manager, &definition.crouch).await,
+        }
+    }
+}
+
+async fn load_buffers(
+    resource_manager: &ResourceManager,
+    paths: &[String],
+) -> Vec<SharedSoundBuffer> {
+    if paths.is_empty() {
+        panic!("every surface needs at least one footstep sound per way of moving!");
+    }
+
+    let mut buffers = Vec::new();
+    for path in paths {
+        buffers.push(
+            resource_manager
+                .request_sound_buffer(path, false)
+                .await
+                .unwrap_or_else(|_| panic!("can't load footstep sound {}", path)),
+        );
+    }
+    buffers
+}
+
+/// the footstep sounds of all surfaces.
+pub struct FootstepSounds {
+    floor: SurfaceSounds,
+    grating: SurfaceSounds,
+    threshold: SurfaceSounds,
+}
+
+impl FootstepSounds {
+    pub async fn load(resource_manager: &ResourceManager, path: &str) -> Self {
+        let definitions: FootstepDefinitions = load_ron(path);
+
+        Self {
+            floor: SurfaceSounds::load(resource_manager, &definitions.floor).await,
+            grating: SurfaceSounds::load(resource_manager, &definitions.grating).await,
+            threshold: SurfaceSounds::load(resource_manager, &definitions.threshold).await,
+        }
+    }
+
+    /// plays a random step for the surface, matching the way the player moves.
+    pub fn play(&self, audio: &mut AudioManager, surface: Surface, state: PlayerState) {
+        let sounds = match surface {
+            Surface::Floor => &self.floor,
+            Surface::Grating => &self.grating,
+            Surface::Threshold => &self.threshold,
+        };
+        let buffers = match state {
+            PlayerState::Running => &sounds.run,
+            PlayerState::Crouching => &sounds.crouch,
+            _ => &sounds.walk,
+        };
+
+        let mut rng = thread_rng();
+        let buffer = buffers.choose(&mut rng).unwrap().clone();
+
+        audio.play(
+            GenericSourceBuilder::new(buffer.into())
+                .with_gain(state.footstep_gain())
+                .with_pitch(rng.gen_range(0.9, 1.05))
+                .with_status(Status::Playing)
+                .build_source()
+                .unwrap(),
+            Bus::Sfx,
+        );
+    }
+}
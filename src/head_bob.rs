@@ -0,0 +1,44 @@
+use crate::settings::HeadBobSettings;
+
+/// moves the camera up and down and sideways in sync with the steps of the player.
+#[derive(Default)]
+pub struct HeadBob {
+    /// fades the bobbing in when starting to walk and out when stopping, 0 to 1
+    intensity: f32,
+}
+
+impl HeadBob {
+    /// how fast `intensity` follows the movement, per second
+    const FADE_SPEED: f32 = 4.0;
+
+    /// `stride_phase` comes from `Player::stride_phase`. Returns the offset of the camera as
+    /// (sideways, up). Feet hit the ground at the lowest point of the curve.
+    pub fn update(
+        &mut self,
+        settings: &HeadBobSettings,
+        stride_phase: f32,
+        moving: bool,
+        running: bool,
+        dt: f32,
+    ) -> (f32, f32) {
+        if !settings.enabled {
+            self.intensity = 0.0;
+            return (0.0, 0.0);
+        }
+
+        let target = match (moving, running) {
+            (false, _) => 0.0,
+            (true, false) => 1.0,
+            (true, true) => settings.run_multiplier,
+        };
+
+        let step = Self::FADE_SPEED * dt;
+        self.intensity += (target - self.intensity).max(-step).min(step);
+
+        // the phase covers two steps, one full sway from left to right and back
+        let up = -(2.0 * stride_phase).cos() * settings.vertical_amplitude;
+        let sideways = stride_phase.sin() * settings.sway_amplitude;
+
+        (sideways * self.intensity, up * self.intensity)
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::HashMap;
+
+use rg3d::event::{KeyboardInput, MouseButton, VirtualKeyCode};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::player::MovementIntent;
+
+/// everything the player can do with a key or mouse button
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Run,
+    Jump,
+    Crouch,
+    LeanLeft,
+    LeanRight,
+    Interact,
+    UseOxygenTank,
+    DropOxygenTank,
+    ToggleFlashlight,
+    InsertBattery,
+    /// held down while turning the crank of the flashlight
+    CrankFlashlight,
+    /// on the game over screen, same station again
+    Restart,
+    /// on the game over screen, with a new seed
+    NewGame,
+    /// opens and closes the settings menu
+    Menu,
+    MenuUp,
+    MenuDown,
+    /// decreases the selected value in the menu
+    MenuLess,
+    /// increases the selected value in the menu
+    MenuMore,
+    /// opens and closes the pause menu
+    Pause,
+    /// only works in the pause menu
+    Quit,
+}
+
+impl Action {
+    const ALL: [Action; 24] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Run,
+        Action::Jump,
+        Action::Crouch,
+        Action::LeanLeft,
+        Action::LeanRight,
+        Action::Interact,
+        Action::UseOxygenTank,
+        Action::DropOxygenTank,
+        Action::ToggleFlashlight,
+        Action::InsertBattery,
+        Action::CrankFlashlight,
+        Action::Restart,
+        Action::NewGame,
+        Action::Menu,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuLess,
+        Action::MenuMore,
+        Action::Pause,
+        Action::Quit,
+    ];
+
+    fn default_bindings(self) -> Vec<Binding> {
+        use VirtualKeyCode::*;
+
+        let key = match self {
+            Action::MoveForward => W,
+            Action::MoveBackward => S,
+            Action::MoveLeft => A,
+            Action::MoveRight => D,
+            Action::Run => LShift,
+            Action::Jump => Space,
+            Action::Crouch => C,
+            // leaning took Q and E, which used to be the keys for oxygen tanks and for using
+            // things. Those moved to T and the left mouse button.
+            Action::LeanLeft => Q,
+            Action::LeanRight => E,
+            Action::Interact => return vec![Binding::Mouse(Button::Left)],
+            Action::UseOxygenTank => T,
+            Action::DropOxygenTank => G,
+            Action::ToggleFlashlight => F,
+            Action::InsertBattery => R,
+            Action::CrankFlashlight => V,
+            Action::Restart => Return,
+            Action::NewGame => N,
+            Action::Menu => Tab,
+            Action::MenuUp => Up,
+            Action::MenuDown => Down,
+            Action::MenuLess => Left,
+            Action::MenuMore => Right,
+            Action::Pause => Escape,
+            Action::Quit => X,
+        };
+        vec![Binding::Key(key)]
+    }
+}
+
+/// a key or mouse button an action can be bound to
+#[derive(Serialize, Copy, Clone, PartialEq, Debug)]
+pub enum Binding {
+    /// a key by what is printed on it, e.g. `Key("Z")` is the key labeled Z, no matter where
+    /// the keyboard layout puts it
+    Key(#[serde(serialize_with = "key_name::serialize")] VirtualKeyCode),
+    /// a key by its position on the keyboard, e.g. to keep WASD in place on an AZERTY
+    /// keyboard. The codes depend on the operating system.
+    ScanCode(u32),
+    Mouse(Button),
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+    Other(u8),
+}
+
+impl From<MouseButton> for Button {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => Button::Left,
+            MouseButton::Right => Button::Right,
+            MouseButton::Middle => Button::Middle,
+            MouseButton::Other(other) => Button::Other(other as u8),
+        }
+    }
+}
+
+impl Binding {
+    fn matches_key(&self, input: &KeyboardInput) -> bool {
+        match *self {
+            Binding::Key(key) => input.virtual_keycode == Some(key),
+            Binding::ScanCode(code) => input.scancode == code,
+            Binding::Mouse(_) => false,
+        }
+    }
+
+    /// short name for prompts like "[E] open door"
+    pub fn name(&self) -> String {
+        match *self {
+            Binding::Key(key) => key_name::name(key).to_owned(),
+            Binding::ScanCode(code) => format!("#{}", code),
+            Binding::Mouse(Button::Left) => "Click".to_owned(),
+            Binding::Mouse(Button::Right) => "Right Click".to_owned(),
+            Binding::Mouse(Button::Middle) => "Middle Click".to_owned(),
+            Binding::Mouse(Button::Other(other)) => format!("Mouse {}", other),
+        }
+    }
+}
+
+/// which keys and buttons trigger which action, part of the user settings. An action can have
+/// any number of bindings and a binding can trigger more than one action.
+#[derive(Serialize, Clone)]
+#[serde(transparent)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+/// `Binding` as it is written in the settings file, with the key still a name
+#[derive(Deserialize)]
+#[serde(rename = "Binding")]
+enum StoredBinding {
+    Key(String),
+    ScanCode(u32),
+    Mouse(Button),
+}
+
+/// a key with a name we don't know, e.g. from a newer version of the game, only loses that
+/// binding instead of the whole settings file.
+impl<'de> Deserialize<'de> for KeyBindings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = HashMap::<Action, Vec<StoredBinding>>::deserialize(deserializer)?;
+
+        let bindings = stored
+            .into_iter()
+            .map(|(action, bindings)| {
+                let bindings = bindings
+                    .into_iter()
+                    .filter_map(|binding| match binding {
+                        StoredBinding::Key(name) => {
+                            let key = key_name::by_name(&name);
+                            if key.is_none() {
+                                println!("unknown key {} bound to {:?}, ignoring it", name, action);
+                            }
+                            key.map(Binding::Key)
+                        }
+                        StoredBinding::ScanCode(code) => Some(Binding::ScanCode(code)),
+                        StoredBinding::Mouse(button) => Some(Binding::Mouse(button)),
+                    })
+                    .collect();
+                (action, bindings)
+            })
+            .collect();
+
+        Ok(Self { bindings })
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL
+                .iter()
+                .map(|&action| (action, action.default_bindings()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// actions missing in the settings file get their default bindings, so new actions work
+    /// with old files. Returns whether something was added.
+    pub fn add_missing(&mut self) -> bool {
+        let mut added = false;
+        for &action in Action::ALL.iter() {
+            if !self.bindings.contains_key(&action) {
+                self.bindings.insert(action, action.default_bindings());
+                added = true;
+            }
+        }
+        added
+    }
+
+    /// all actions bound to the key
+    pub fn key_actions(&self, input: &KeyboardInput) -> Vec<Action> {
+        self.actions(|binding| binding.matches_key(input))
+    }
+
+    /// all actions bound to the mouse button
+    pub fn mouse_actions(&self, button: MouseButton) -> Vec<Action> {
+        let button = Button::from(button);
+        self.actions(|binding| *binding == Binding::Mouse(button))
+    }
+
+    fn actions(&self, matches: impl Fn(&Binding) -> bool) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|(_, bindings)| bindings.iter().any(&matches))
+            .map(|(&action, _)| action)
+            .collect()
+    }
+
+    /// name of the first binding of the action, for prompts
+    pub fn name(&self, action: Action) -> String {
+        self.bindings
+            .get(&action)
+            .and_then(|bindings| bindings.first())
+            .map_or("unbound".to_owned(), Binding::name)
+    }
+}
+
+/// what the player is doing with the controls right now, driven by the actions of the keys and
+/// buttons they press.
+#[derive(Default)]
+pub struct InputController {
+    pub move_left: bool,
+    pub move_right: bool,
+    pub move_forward: bool,
+    pub move_backward: bool,
+    pub run: bool,
+    pub jump: bool,
+    pub crouch: bool,
+    pub lean_left: bool,
+    pub lean_right: bool,
+    /// set when the use key was released, cleared by the tick handling it
+    pub interact: bool,
+    /// like `interact`, for using and dropping an oxygen tank from the inventory
+    pub use_tank: bool,
+    pub drop_tank: bool,
+    pub insert_battery: bool,
+    /// held down while turning the crank of the flashlight
+    pub crank: bool,
+}
+
+impl InputController {
+    /// a key or button bound to `action` went down or up. Actions that don't describe the
+    /// state of the controls, like quitting the game, are ignored.
+    pub fn apply(&mut self, action: Action, pressed: bool) {
+        match action {
+            Action::MoveForward => self.move_forward = pressed,
+            Action::MoveBackward => self.move_backward = pressed,
+            Action::MoveLeft => self.move_left = pressed,
+            Action::MoveRight => self.move_right = pressed,
+            Action::Run => self.run = pressed,
+            Action::Jump => self.jump = pressed,
+            Action::Crouch => self.crouch = pressed,
+            Action::LeanLeft => self.lean_left = pressed,
+            Action::LeanRight => self.lean_right = pressed,
+            Action::CrankFlashlight => self.crank = pressed,
+            Action::Interact => self.interact |= !pressed,
+            Action::UseOxygenTank => self.use_tank |= !pressed,
+            Action::DropOxygenTank => self.drop_tank |= !pressed,
+            Action::InsertBattery => self.insert_battery |= !pressed,
+            Action::ToggleFlashlight
+            | Action::Restart
+            | Action::NewGame
+            | Action::Menu
+            | Action::MenuUp
+            | Action::MenuDown
+            | Action::MenuLess
+            | Action::MenuMore
+            | Action::Pause
+            | Action::Quit => (),
+        }
+    }
+
+    /// what the controls ask the player to do with their feet
+    pub fn intent(&self) -> MovementIntent {
+        MovementIntent {
+            moving: self.move_forward || self.move_backward || self.move_left || self.move_right,
+            run: self.run,
+            crouch: self.crouch,
+        }
+    }
+}
+
+/// keys are stored by their name in the settings file, which is the name of their
+/// `VirtualKeyCode`
+mod key_name {
+    use rg3d::event::VirtualKeyCode;
+    use serde::Serializer;
+
+    macro_rules! key_names {
+        ($($key:ident),* $(,)?) => {
+            /// no catch-all, so a key added to the engine can't go without a name
+            pub fn name(key: VirtualKeyCode) -> &'static str {
+                match key {
+                    $(VirtualKeyCode::$key => stringify!($key),)*
+                }
+            }
+
+            pub fn by_name(name: &str) -> Option<VirtualKeyCode> {
+                match name {
+                    $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                    _ => None,
+                }
+            }
+
+            #[cfg(test)]
+            pub const ALL: &[VirtualKeyCode] = &[$(VirtualKeyCode::$key,)*];
+        };
+    }
+
+    key_names! {
+        Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Escape,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24,
+        Snapshot, Scroll, Pause,
+        Insert, Home, Delete, End, PageDown, PageUp,
+        Left, Up, Right, Down,
+        Back, Return, Space, Compose, Caret, Numlock,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        NumpadAdd, NumpadDivide, NumpadDecimal, NumpadComma, NumpadEnter, NumpadEquals,
+        NumpadMultiply, NumpadSubtract,
+        AbntC1, AbntC2, Apostrophe, Apps, Asterisk, At, Ax, Backslash, Calculator, Capital, Colon,
+        Comma, Convert, Equals, Grave, Kana, Kanji, LAlt, LBracket, LControl, LShift, LWin, Mail,
+        MediaSelect, MediaStop, Minus, Mute, MyComputer, NavigateForward, NavigateBackward,
+        NextTrack, NoConvert, OEM102, Period, PlayPause, Plus, Power, PrevTrack, RAlt, RBracket,
+        RControl, RShift, RWin, Semicolon, Slash, Sleep, Stop, Sysrq, Tab, Underline, Unlabeled,
+        VolumeDown, VolumeUp, Wake, WebBack, WebFavorites, WebForward, WebHome, WebRefresh,
+        WebSearch, WebStop, Yen, Copy, Paste, Cut,
+    }
+
+    pub fn serialize<S: Serializer>(
+        key: &VirtualKeyCode,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(name(*key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_round_trip() {
+        for &key in key_name::ALL {
+            assert_eq!(key_name::by_name(key_name::name(key)), Some(key));
+        }
+
+        let bindings = KeyBindings::default();
+        let text = ron::ser::to_string(&bindings).unwrap();
+        let loaded: KeyBindings = ron::de::from_str(&text).unwrap();
+        for &action in Action::ALL.iter() {
+            assert_eq!(loaded.bindings[&action], bindings.bindings[&action]);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_only_lose_their_binding() {
+        let text = r#"{
+            MoveForward: [Key("W"), Key("Hyper"), ScanCode(17)],
+            Jump: [Key("Semicolon")],
+            Interact: [Mouse(Left)],
+        }"#;
+        let bindings: KeyBindings = ron::de::from_str(text).unwrap();
+
+        assert_eq!(
+            bindings.bindings[&Action::MoveForward],
+            vec![Binding::Key(VirtualKeyCode::W), Binding::ScanCode(17)]
+        );
+        assert_eq!(
+            bindings.bindings[&Action::Jump],
+            vec![Binding::Key(VirtualKeyCode::Semicolon)]
+        );
+        assert_eq!(
+            bindings.bindings[&Action::Interact],
+            vec![Binding::Mouse(Button::Left)]
+        );
+    }
+
+    #[test]
+    fn default_bindings_are_unique() {
+        let bindings: Vec<(Action, Binding)> = Action::ALL
+            .iter()
+            .flat_map(|&action| {
+                action
+                    .default_bindings()
+                    .into_iter()
+                    .map(move |binding| (action, binding))
+            })
+            .collect();
+
+        for (i, (action, binding)) in bindings.iter().enumerate() {
+            for (other, other_binding) in &bindings[i + 1..] {
+                assert_ne!(binding, other_binding, "{:?} and {:?}", action, other);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use rg3d::core::color::Color;
+use rg3d::core::pool::Handle;
+use rg3d::physics::geometry::ColliderHandle;
+use rg3d::physics::na::Vector3;
+use rg3d::scene::node::Node;
+use rg3d::scene::Scene;
+
+use crate::inventory::Item;
+
+/// something in the level the player can use. They are registered by the scene builder and
+/// targeted by looking at them, see `World::find_target`.
+pub struct Interactable {
+    /// the node that gets highlighted while targeted
+    pub node: Handle<Node>,
+    /// world position of the point the player has to look at
+    pub position: Vector3<f32>,
+    pub action: Action,
+}
+
+pub enum Action {
+    /// an item lying around. `id` is its index among all pickups of its chunk. It stays the
+    /// same when the chunk is loaded again, so items that were taken don't come back. Items
+    /// dropped by the player don't have one.
+    Pickup {
+        item: Item,
+        id: Option<usize>,
+    },
+    Door(Door),
+    Terminal {
+        message: String,
+    },
+    /// switches all lights of a room
+    LightSwitch {
+        lights: Vec<Handle<Node>>,
+        on: bool,
+    },
+}
+
+pub struct Door {
+    /// tile of the door, relative to its chunk
+    pub tile: (usize, usize),
+    pub size: Vector3<f32>,
+    /// blocks the doorway while the door is closed, `None` while it's open
+    pub collider: Option<ColliderHandle>,
+}
+
+impl Door {
+    pub fn is_open(&self) -> bool {
+        self.collider.is_none()
+    }
+}
+
+/// what happened when the player used something. The world handles everything it can by itself,
+/// the rest is up to the caller.
+pub enum Outcome {
+    Nothing,
+    /// the interactable was removed from the world and belongs into the inventory now
+    PickedUp(Item),
+    Message(String),
+}
+
+impl Interactable {
+    /// interactables farther away from the camera can't be used, in units
+    pub const REACH: f32 = 1.5;
+    /// the interactable is targeted if the view ray passes within this distance of `position`
+    const TARGET_RADIUS: f32 = 0.3;
+
+    /// the item the player gets for using this, if any
+    pub fn item(&self) -> Option<Item> {
+        match self.action {
+            Action::Pickup { item, .. } => Some(item),
+            _ => None,
+        }
+    }
+
+    /// shown while the interactable is targeted. `key` is the name of the key to use it.
+    pub fn prompt(&self, key: &str) -> String {
+        let text = match &self.action {
+            Action::Pickup { item, .. } => format!("take {}", item.name()),
+            Action::Door(door) if door.is_open() => "close door".to_owned(),
+            Action::Door(_) => "open door".to_owned(),
+            Action::Terminal { .. } => "read terminal".to_owned(),
+            Action::LightSwitch { on: true, .. } => "switch lights off".to_owned(),
+            Action::LightSwitch { on: false, .. } => "switch lights on".to_owned(),
+        };
+        format!("[{}] {}", key, text)
+    }
+
+    /// distance along the ray from `origin` in (normalized) `direction` at which it passes the
+    /// interactable, if it does so within reach.
+    pub fn hit_distance(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<f32> {
+        let to_target = self.position - origin;
+        let along = to_target.dot(&direction);
+        if along < 0.0 || along > Self::REACH {
+            return None;
+        }
+
+        let closest = origin + direction.scale(along);
+        if (self.position - closest).norm() <= Self::TARGET_RADIUS {
+            Some(along)
+        } else {
+            None
+        }
+    }
+}
+
+/// tints the meshes of whatever the player is targeting, so they see what they are about to
+/// use. The colors the meshes had before are restored when the target changes.
+#[derive(Default)]
+pub struct Highlight {
+    node: Option<Handle<Node>>,
+    /// original colors of the surfaces of every mesh below `node`
+    colors: Vec<(Handle<Node>, Vec<Color>)>,
+}
+
+impl Highlight {
+    /// highlights `node` instead of the last one, `None` just removes the highlight.
+    pub fn set(&mut self, scene: &mut Scene, node: Option<Handle<Node>>) {
+        if node == self.node {
+            return;
+        }
+
+        for (handle, colors) in self.colors.drain(..) {
+            // picked up items are gone already
+            if !scene.graph.is_valid_handle(handle) {
+                continue;
+            }
+            if let Node::Mesh(mesh) = &mut scene.graph[handle] {
+                for (surface, &color) in mesh.surfaces_mut().iter_mut().zip(colors.iter()) {
+                    surface.set_color(color);
+                }
+            }
+        }
+
+        if let Some(node) = node {
+            let handles = scene.graph.traverse_handle_iter(node).collect::<Vec<_>>();
+            for handle in handles {
+                if let Node::Mesh(mesh) = &mut scene.graph[handle] {
+                    let colors = mesh.surfaces().iter().map(|s| s.color()).collect();
+                    for surface in mesh.surfaces_mut() {
+                        surface.set_color(Color::opaque(255, 230, 140));
+                    }
+                    self.colors.push((handle, colors));
+                }
+            }
+        }
+
+        self.node = node;
+    }
+}
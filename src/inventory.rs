@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum KeycardColor {
+    Red,
+    Green,
+    Blue,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Tool {
+    Wrench,
+    Crowbar,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Item {
+    Keycard(KeycardColor),
+    OxygenTank,
+    Battery,
+    Tool(Tool),
+}
+
+impl Item {
+    /// how many of this item the player can carry at once
+    pub fn max_count(self) -> usize {
+        match self {
+            Item::Keycard(_) | Item::Tool(_) => 1,
+            Item::OxygenTank => 2,
+            Item::Battery => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Item::Keycard(KeycardColor::Red) => "red keycard",
+            Item::Keycard(KeycardColor::Green) => "green keycard",
+            Item::Keycard(KeycardColor::Blue) => "blue keycard",
+            Item::OxygenTank => "oxygen tank",
+            Item::Battery => "battery",
+            Item::Tool(Tool::Wrench) => "wrench",
+            Item::Tool(Tool::Crowbar) => "crowbar",
+        }
+    }
+}
+
+/// something happened to the inventory. The HUD polls them with `Inventory::poll_event`.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum InventoryEvent {
+    Added(Item),
+    Removed(Item),
+    /// the item couldn't be added, the player carries too many of them already
+    Full(Item),
+}
+
+/// everything the player carries, in the order it was picked up. Serialized with save games.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Inventory {
+    items: Vec<(Item, usize)>,
+    #[serde(skip)]
+    events: VecDeque<InventoryEvent>,
+}
+
+impl Inventory {
+    /// different kinds of items the player can carry at once
+    pub const SLOTS: usize = 6;
+
+    pub fn count(&self, item: Item) -> usize {
+        self.items
+            .iter()
+            .find(|&&(i, _)| i == item)
+            .map_or(0, |&(_, count)| count)
+    }
+
+    pub fn contains(&self, item: Item) -> bool {
+        self.count(item) > 0
+    }
+
+    pub fn can_add(&self, item: Item) -> bool {
+        match self.items.iter().find(|&&(i, _)| i == item) {
+            Some(&(_, count)) => count < item.max_count(),
+            None => self.items.len() < Self::SLOTS,
+        }
+    }
+
+    /// picks up the item. Returns false if there is no room for it.
+    pub fn add(&mut self, item: Item) -> bool {
+        if !self.can_add(item) {
+            self.events.push_back(InventoryEvent::Full(item));
+            return false;
+        }
+
+        match self.items.iter_mut().find(|(i, _)| *i == item) {
+            Some((_, count)) => *count += 1,
+            None => self.items.push((item, 1)),
+        }
+        self.events.push_back(InventoryEvent::Added(item));
+        true
+    }
+
+    /// takes one of the item out of the inventory to use or drop it. Returns false if the
+    /// player doesn't have any.
+    pub fn remove(&mut self, item: Item) -> bool {
+        let index = match self.items.iter().position(|&(i, _)| i == item) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        self.items[index].1 -= 1;
+        if self.items[index].1 == 0 {
+            self.items.remove(index);
+        }
+        self.events.push_back(InventoryEvent::Removed(item));
+        true
+    }
+
+    /// all items with their count, in the order they were picked up
+    pub fn items(&self) -> &[(Item, usize)] {
+        &self.items
+    }
+
+    /// the oldest event that wasn't polled yet
+    pub fn poll_event(&mut self) -> Option<InventoryEvent> {
+        self.events.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(inventory: &mut Inventory) -> Vec<InventoryEvent> {
+        std::iter::from_fn(|| inventory.poll_event()).collect()
+    }
+
+    #[test]
+    fn items_stack_up_to_their_max_count() {
+        let mut inventory = Inventory::default();
+
+        assert!(inventory.add(Item::OxygenTank));
+        assert!(inventory.add(Item::OxygenTank));
+        assert!(!inventory.add(Item::OxygenTank));
+        assert_eq!(inventory.count(Item::OxygenTank), 2);
+
+        assert!(inventory.add(Item::Keycard(KeycardColor::Red)));
+        assert!(!inventory.add(Item::Keycard(KeycardColor::Red)));
+        // another color is another item
+        assert!(inventory.add(Item::Keycard(KeycardColor::Blue)));
+        assert_eq!(inventory.items().len(), 3);
+    }
+
+    #[test]
+    fn no_new_kind_of_item_once_all_slots_are_taken() {
+        let mut inventory = Inventory::default();
+        let items = [
+            Item::Keycard(KeycardColor::Red),
+            Item::Keycard(KeycardColor::Green),
+            Item::Keycard(KeycardColor::Blue),
+            Item::OxygenTank,
+            Item::Battery,
+            Item::Tool(Tool::Wrench),
+        ];
+        for &item in &items {
+            assert!(inventory.add(item));
+        }
+        assert_eq!(inventory.items().len(), Inventory::SLOTS);
+
+        assert!(!inventory.can_add(Item::Tool(Tool::Crowbar)));
+        assert!(!inventory.add(Item::Tool(Tool::Crowbar)));
+        // more of an item the player already has still fits
+        assert!(inventory.add(Item::Battery));
+
+        // and removing the last of an item frees its slot
+        assert!(inventory.remove(Item::OxygenTank));
+        assert!(inventory.add(Item::Tool(Tool::Crowbar)));
+    }
+
+    #[test]
+    fn removing_what_isnt_there_fails() {
+        let mut inventory = Inventory::default();
+
+        assert!(!inventory.remove(Item::Battery));
+        assert!(inventory.add(Item::Battery));
+        assert!(inventory.add(Item::Battery));
+        assert!(inventory.remove(Item::Battery));
+        assert_eq!(inventory.count(Item::Battery), 1);
+        assert!(inventory.remove(Item::Battery));
+        assert!(!inventory.contains(Item::Battery));
+        assert!(inventory.items().is_empty());
+        assert!(!inventory.remove(Item::Battery));
+    }
+
+    #[test]
+    fn events_come_in_order() {
+        let mut inventory = Inventory::default();
+
+        inventory.add(Item::OxygenTank);
+        inventory.add(Item::OxygenTank);
+        inventory.add(Item::OxygenTank);
+        inventory.remove(Item::OxygenTank);
+        // failed removals don't tell the HUD anything
+        inventory.remove(Item::Battery);
+
+        assert_eq!(
+            events(&mut inventory),
+            vec![
+                InventoryEvent::Added(Item::OxygenTank),
+                InventoryEvent::Added(Item::OxygenTank),
+                InventoryEvent::Full(Item::OxygenTank),
+                InventoryEvent::Removed(Item::OxygenTank),
+            ]
+        );
+        assert_eq!(inventory.poll_event(), None);
+    }
+
+    #[test]
+    fn survives_a_save_game() {
+        let mut inventory = Inventory::default();
+        inventory.add(Item::Tool(Tool::Crowbar));
+        inventory.add(Item::Battery);
+        inventory.add(Item::Battery);
+        inventory.add(Item::Keycard(KeycardColor::Green));
+
+        let text = ron::ser::to_string(&inventory).unwrap();
+        let mut loaded: Inventory = ron::de::from_str(&text).unwrap();
+
+        assert_eq!(loaded.items(), inventory.items());
+        // events are for the HUD of the running game only
+        assert_eq!(loaded.poll_event(), None);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::collision::{move_and_slide, CollisionGrid};
+
+/// shifts and rolls the camera sideways, so the player can peek around corners and into rooms
+/// without stepping out.
+#[derive(Default)]
+pub struct Lean {
+    /// from -1 (fully leaning left) to 1 (fully leaning right)
+    amount: f32,
+}
+
+impl Lean {
+    /// how far the camera moves sideways when fully leaning, in units
+    const MAX_OFFSET: f32 = 0.35;
+    const MAX_ROLL: f32 = 12.0;
+    /// `amount` per second
+    const SPEED: f32 = 4.0;
+    /// the camera keeps this distance to walls
+    const CAMERA_RADIUS: f32 = 0.1;
+
+    /// `direction` is -1 to lean left, 1 to lean right and 0 to stand up straight. `position` is
+    /// where the player stands and `right` the (normalized) direction to their right on the
+    /// ground, both as (x, z). The camera stops before it would enter a wall of `grid`.
+    pub fn update(
+        &mut self,
+        grid: &impl CollisionGrid,
+        direction: f32,
+        position: (f32, f32),
+        right: (f32, f32),
+        dt: f32,
+    ) {
+        let step = Self::SPEED * dt;
+        self.amount += (direction - self.amount).max(-step).min(step);
+
+        if self.amount == 0.0 {
+            return;
+        }
+
+        // how far the camera gets before it hits a wall
+        let wanted = self.amount * Self::MAX_OFFSET;
+        let (x, z) = move_and_slide(
+            grid,
+            position,
+            (right.0 * wanted, right.1 * wanted),
+            Self::CAMERA_RADIUS,
+        );
+        let room = ((x - position.0) * right.0 + (z - position.1) * right.1).abs();
+
+        let max_amount = room / Self::MAX_OFFSET;
+        self.amount = self.amount.max(-max_amount).min(max_amount);
+    }
+
+    /// sideways offset of the camera, positive to the right
+    pub fn offset(&self) -> f32 {
+        self.amount * Self::MAX_OFFSET
+    }
+
+    /// roll of the camera around the view direction, in degrees. Positive to the right.
+    pub fn roll(&self) -> f32 {
+        self.amount * Self::MAX_ROLL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::tests::{two_rooms, ClosedDoor};
+    use crate::level_generator::Level;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// leans for a second, long enough to lean out completely
+    fn lean(
+        grid: &impl CollisionGrid,
+        direction: f32,
+        position: (f32, f32),
+        right: (f32, f32),
+    ) -> Lean {
+        let mut lean = Lean::default();
+        for _ in 0..60 {
+            lean.update(grid, direction, position, right, DT);
+        }
+        lean
+    }
+
+    #[test]
+    fn full_lean_in_the_open() {
+        let level = Level::parse(
+            "#######
+             #.....#
+             #.....#
+             #.....#
+             #.....#
+             #.....#
+             #######",
+        );
+
+        let right = lean(&level, 1.0, (3.0, 3.0), (1.0, 0.0));
+        assert!((right.offset() - Lean::MAX_OFFSET).abs() < 0.001);
+        assert!((right.roll() - Lean::MAX_ROLL).abs() < 0.001);
+
+        let left = lean(&level, -1.0, (3.0, 3.0), (1.0, 0.0));
+        assert!((left.offset() + Lean::MAX_OFFSET).abs() < 0.001);
+    }
+
+    #[test]
+    fn lean_stops_at_a_wall() {
+        let level = Level::parse(
+            "#######
+             #.....#
+             #.....#
+             #.....#
+             #######",
+        );
+
+        // standing right at the wall on the left, the camera keeps its distance to it
+        let left = lean(&level, -1.0, (0.7, 2.0), (1.0, 0.0));
+        let room = 0.7 - 0.5 - Lean::CAMERA_RADIUS;
+        assert!(
+            (left.offset() + room).abs() < 0.01,
+            "offset is {}",
+            left.offset()
+        );
+
+        // the other way is free
+        let right = lean(&level, 1.0, (0.7, 2.0), (1.0, 0.0));
+        assert!((right.offset() - Lean::MAX_OFFSET).abs() < 0.001);
+    }
+
+    #[test]
+    fn no_lean_through_a_closed_door() {
+        // in front of the door at (3, 3), looking along it with the door to the right
+        let (position, right) = ((3.0, 2.3), (0.0, 1.0));
+        let room = 2.5 - position.1 - Lean::CAMERA_RADIUS;
+
+        let open = lean(&two_rooms(), 1.0, position, right);
+        assert!((open.offset() - Lean::MAX_OFFSET).abs() < 0.001);
+
+        let closed_door = ClosedDoor {
+            level: two_rooms(),
+            door: (3, 3),
+        };
+        let closed = lean(&closed_door, 1.0, position, right);
+        assert!(
+            (closed.offset() - room).abs() < 0.01,
+            "offset is {}",
+            closed.offset()
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+use crate::flashlight::FlashlightOptions;
+use crate::level_generator::RoomOptions;
+use crate::lighting::LightingOptions;
+use crate::occlusion::OcclusionOptions;
+use crate::oxygen::OxygenOptions;
+use crate::reverb::ReverbOptions;
+use crate::ron_file::load_ron;
+
+/// tuning of the generated station, loaded from `assets/level.ron`
+#[derive(Deserialize, Clone)]
+pub struct LevelConfig {
+    pub rooms: RoomOptions,
+    pub lighting: LightingOptions,
+    pub oxygen: OxygenOptions,
+    pub flashlight: FlashlightOptions,
+    pub reverb: ReverbOptions,
+    pub occlusion: OcclusionOptions,
+}
+
+impl LevelConfig {
+    pub fn load(path: &str) -> Self {
+        load_ron(path)
+    }
+}
@@ -2,9 +2,12 @@ use std::cmp::min;
 
 use crate::level_generator::FieldType::{Corridor, Door, Empty};
 use num::{signum, Integer};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::Deserialize;
 
+#[derive(Copy, Clone, Deserialize)]
 pub struct RoomOptions {
     pub max_rooms: usize,
     pub max_attempts: usize,
@@ -94,11 +97,16 @@ impl Level {
         vec![vec![Field::default(); height]; width]
     }
 
+    /// generates a dungeon. All randomness comes from `rng`, so the same seed always yields the
+    /// same level. `exits` are tiles on the border of the map that get opened up, so the level
+    /// can be connected to its neighbours.
     pub fn create_dungeon(
         width: usize,
         height: usize,
         room_options: RoomOptions,
         room_identifier: FieldType,
+        exits: &[(usize, usize)],
+        rng: &mut StdRng,
     ) -> Self {
         let mut level = Level::create_rooms(
             width,
@@ -108,11 +116,16 @@ impl Level {
             room_options.min_size,
             room_options.max_size,
             room_identifier,
+            rng,
         );
 
-        level.add_maze();
+        level.add_maze(rng);
 
-        level.add_doors();
+        level.add_doors(rng);
+
+        // must happen before removing dead ends, otherwise the corridors leading to the exits
+        // would be removed.
+        level.add_exits(exits);
 
         loop {
             let removed = level.remove_dead_ends();
@@ -132,6 +145,7 @@ impl Level {
         min_size: usize,
         max_size: usize,
         room_identifier: FieldType,
+        rng: &mut StdRng,
     ) -> Self {
         let mut map = Level::init_map(width, height);
 
@@ -139,12 +153,12 @@ impl Level {
 
         for _ in 0..max_rooms {
             'attempts: for _ in 0..max_attempts {
-                let x = gen_odd_range(0, width - 1);
-                let x_extent = gen_even_range(min_size, max_size);
+                let x = gen_odd_range(rng, 0, width - 1);
+                let x_extent = gen_even_range(rng, min_size, max_size);
                 let x_extent = min(x_extent, width - x - 2);
 
-                let y = gen_odd_range(0, height - 1);
-                let y_extent = gen_even_range(min_size, max_size);
+                let y = gen_odd_range(rng, 0, height - 1);
+                let y_extent = gen_even_range(rng, min_size, max_size);
                 let y_extent = min(y_extent, height - y - 2);
 
                 if x_extent < 2 || y_extent < 2 {
@@ -212,12 +226,10 @@ impl Level {
     }
 
     /// creates a maze using randomized depth-first search
-    fn add_maze(&mut self) {
+    fn add_maze(&mut self, rng: &mut StdRng) {
         let width = self.map.len();
         let height = self.map[0].len();
 
-        let mut rng = thread_rng();
-
         let mut corridors = Vec::new();
 
         for x in (0..width).filter(Integer::is_odd) {
@@ -285,19 +297,17 @@ impl Level {
         self.corridors = corridors;
     }
 
-    fn add_doors(&mut self) {
+    fn add_doors(&mut self, rng: &mut StdRng) {
         let mut regions = Vec::new();
         regions.clone_from(&self.rooms);
         regions.append(&mut self.corridors.clone());
 
-        let mut rng = thread_rng();
-
         // randomize walk-order, so the doors aren't always in the upper left area...
         let mut x_order = (2..self.width() - 2).collect::<Vec<usize>>();
         let mut y_order = (2..self.height() - 2).collect::<Vec<usize>>();
 
-        x_order.shuffle(&mut rng);
-        y_order.shuffle(&mut rng);
+        x_order.shuffle(rng);
+        y_order.shuffle(rng);
 
         // all regions are seperated now. find connectors and connect them.
         for &x in &x_order {
@@ -360,6 +370,23 @@ impl Level {
         }
     }
 
+    /// opens up tiles on the border of the map. Every border tile must sit next to an odd cell,
+    /// because those are always part of the maze or a room.
+    fn add_exits(&mut self, exits: &[(usize, usize)]) {
+        for &(x, y) in exits {
+            assert!(
+                x == 0 || y == 0 || x == self.width() - 1 || y == self.height() - 1,
+                "exit ({}, {}) is not on the border of the map!",
+                x,
+                y
+            );
+
+            self.map[x][y].typ = Corridor;
+        }
+
+        self.corridors.push(exits.to_vec());
+    }
+
     fn remove_dead_ends(&mut self) -> usize {
         let mut corridors = self.corridors.clone();
 
@@ -386,9 +413,8 @@ impl Level {
     }
 }
 
-fn gen_odd_range(lower: usize, upper: usize) -> usize {
+fn gen_odd_range(rng: &mut StdRng, lower: usize, upper: usize) -> usize {
     let mut x: usize;
-    let mut rng = thread_rng();
 
     loop {
         x = rng.gen_range(lower, upper);
@@ -400,9 +426,8 @@ fn gen_odd_range(lower: usize, upper: usize) -> usize {
     x
 }
 
-fn gen_even_range(lower: usize, upper: usize) -> usize {
+fn gen_even_range(rng: &mut StdRng, lower: usize, upper: usize) -> usize {
     let mut x: usize;
-    let mut rng = thread_rng();
 
     loop {
         x = rng.gen_range(lower, upper);
@@ -413,3 +438,94 @@ fn gen_even_range(lower: usize, upper: usize) -> usize {
 
     x
 }
+
+#[cfg(test)]
+impl Level {
+    /// builds a level by hand for tests. Every line is a row of the map, from `y = 0` downwards,
+    /// indentation is ignored: `#` is solid, `.` the floor of a room, `,` a corridor and `+` a
+    /// door. Every patch of floor tiles becomes a room.
+    pub fn parse(text: &str) -> Self {
+        let rows = text.lines().map(str::trim).collect::<Vec<_>>();
+
+        let mut map = vec![vec![Field::default(); rows.len()]; rows[0].len()];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                map[x][y].typ = match tile {
+                    '#' => Empty,
+                    '.' => FieldType::Floor,
+                    ',' => Corridor,
+                    '+' => Door,
+                    _ => panic!("unknown tile {}", tile),
+                };
+            }
+        }
+
+        let mut level = Level {
+            rooms: Vec::new(),
+            corridors: Vec::new(),
+            map,
+        };
+
+        let mut seen = vec![vec![false; level.height()]; level.width()];
+        for x in 0..level.width() {
+            for y in 0..level.height() {
+                if seen[x][y] || level.map[x][y].typ != FieldType::Floor {
+                    continue;
+                }
+
+                seen[x][y] = true;
+                let mut room = vec![(x, y)];
+                let mut next = 0;
+                while next < room.len() {
+                    for (nx, ny) in level.get_neighbours(room[next], 1) {
+                        if !seen[nx][ny] && level.map[nx][ny].typ == FieldType::Floor {
+                            seen[nx][ny] = true;
+                            room.push((nx, ny));
+                        }
+                    }
+                    next += 1;
+                }
+                level.rooms.push(room);
+            }
+        }
+
+        level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// the size and rooms of a chunk, like in `assets/level.ron`
+    fn generate(seed: u64, exits: &[(usize, usize)]) -> Level {
+        let options = RoomOptions {
+            max_rooms: 6,
+            max_attempts: 125,
+            min_size: 4,
+            max_size: 10,
+        };
+        let mut rng = StdRng::seed_from_u64(seed);
+        Level::create_dungeon(31, 31, options, FieldType::Floor, exits, &mut rng)
+    }
+
+    const EXITS: [(usize, usize); 4] = [(0, 5), (30, 7), (9, 0), (13, 30)];
+
+    #[test]
+    fn same_seed_same_level() {
+        for seed in 0..20 {
+            let a = generate(seed, &EXITS);
+            let b = generate(seed, &EXITS);
+
+            assert!(a.map == b.map, "the maps of seed {} differ", seed);
+            assert_eq!(a.rooms, b.rooms);
+            assert_eq!(a.corridors, b.corridors);
+        }
+    }
+
+    #[test]
+    fn other_seed_other_level() {
+        assert!(generate(1, &EXITS).map != generate(2, &EXITS).map);
+    }
+}
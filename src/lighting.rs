@@ -0,0 +1,449 @@
+use std::collections::VecDeque;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rg3d::core::pool::Handle;
+use rg3d::scene::node::Node;
+use rg3d::scene::Scene;
+use serde::Deserialize;
+
+use crate::level_generator::{FieldType, Level};
+
+/// per level settings for `plan_lights`, see `assets/level.ron`
+#[derive(Deserialize, Clone)]
+pub struct LightingOptions {
+    /// distance in tiles, walked along the corridors, between two corridor lights
+    pub corridor_spacing: usize,
+    pub corridor_radius: f32,
+    /// a room gets one light for every this many tiles, but at least one
+    pub room_tiles_per_light: usize,
+    pub room_radius: f32,
+    /// maximum number of lights in all loaded chunks that cast shadows, the ones closest to the
+    /// player get them
+    pub max_shadow_casters: usize,
+    /// chance of a room being completely dark
+    pub dark_room_chance: f64,
+    /// chance of a room or corridor light being a red emergency light
+    pub emergency_chance: f64,
+    /// chance of a room or corridor light being broken and flickering
+    pub broken_chance: f64,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum LightRole {
+    Normal,
+    Emergency,
+    Flickering,
+}
+
+#[derive(Debug)]
+pub struct PlannedLight {
+    /// position in tiles, relative to the level
+    pub position: (f32, f32),
+    pub height: f32,
+    pub radius: f32,
+    pub role: LightRole,
+    /// index into `Level::rooms` for room lights
+    pub room: Option<usize>,
+}
+
+/// decides where the lights of a level go and what they look like.
+pub fn plan_lights(
+    level: &Level,
+    options: &LightingOptions,
+    rng: &mut StdRng,
+) -> Vec<PlannedLight> {
+    let mut lights = Vec::new();
+
+    for (index, room) in level.rooms.iter().enumerate() {
+        if rng.gen_bool(options.dark_room_chance) {
+            continue;
+        }
+
+        // all lights of a room share the same role, half a room flickering looks odd
+        let role = roll_role(options, rng);
+
+        for position in room_light_positions(room, options.room_tiles_per_light) {
+            lights.push(PlannedLight {
+                position,
+                height: 2.0,
+                radius: options.room_radius,
+                role,
+                room: Some(index),
+            });
+        }
+    }
+
+    for (x, y) in corridor_light_tiles(level, options.corridor_spacing) {
+        lights.push(PlannedLight {
+            position: (x as f32, y as f32),
+            height: 0.3,
+            radius: options.corridor_radius,
+            role: roll_role(options, rng),
+            room: None,
+        });
+    }
+
+    lights
+}
+
+fn roll_role(options: &LightingOptions, rng: &mut StdRng) -> LightRole {
+    if rng.gen_bool(options.emergency_chance) {
+        LightRole::Emergency
+    } else if rng.gen_bool(options.broken_chance) {
+        LightRole::Flickering
+    } else {
+        LightRole::Normal
+    }
+}
+
+/// spreads the lights of a room evenly over a grid covering the room.
+fn room_light_positions(room: &[(usize, usize)], tiles_per_light: usize) -> Vec<(f32, f32)> {
+    let min_x = room.iter().map(|t| t.0).min().unwrap();
+    let max_x = room.iter().map(|t| t.0).max().unwrap();
+    let min_y = room.iter().map(|t| t.1).min().unwrap();
+    let max_y = room.iter().map(|t| t.1).max().unwrap();
+
+    let width = (max_x - min_x + 1) as f32;
+    let height = (max_y - min_y + 1) as f32;
+
+    let tiles_per_light = tiles_per_light.max(1);
+    let count = ((room.len() + tiles_per_light - 1) / tiles_per_light).max(1);
+
+    let columns = ((count as f32 * width / height).sqrt().round() as usize).max(1);
+    let rows = (count + columns - 1) / columns;
+
+    let mut positions = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            // tiles are centered on their coordinates, so the room starts half a tile earlier
+            positions.push((
+                min_x as f32 - 0.5 + (column as f32 + 0.5) * width / columns as f32,
+                min_y as f32 - 0.5 + (row as f32 + 0.5) * height / rows as f32,
+            ));
+        }
+    }
+
+    positions
+}
+
+/// walks through the corridors breadth first and puts a light on every tile that is at least
+/// `spacing` steps away from all previous lights. Doors are walked through, but never get a
+/// light, they are filled by the door itself.
+fn corridor_light_tiles(level: &Level, spacing: usize) -> Vec<(usize, usize)> {
+    let is_corridor = |(x, y): (usize, usize)| {
+        matches!(level.map[x][y].typ, FieldType::Corridor | FieldType::Door)
+    };
+
+    // walking distance to the closest light
+    let mut distance = vec![vec![usize::MAX; level.height()]; level.width()];
+    let mut visited = vec![vec![false; level.height()]; level.width()];
+
+    let mut lights = Vec::new();
+
+    for x in 0..level.width() {
+        for y in 0..level.height() {
+            if visited[x][y] || !is_corridor((x, y)) {
+                continue;
+            }
+
+            let mut queue = VecDeque::new();
+            queue.push_back((x, y));
+            visited[x][y] = true;
+
+            while let Some(tile) = queue.pop_front() {
+                if distance[tile.0][tile.1] >= spacing
+                    && level.map[tile.0][tile.1].typ == FieldType::Corridor
+                {
+                    lights.push(tile);
+                    mark_surroundings(level, &is_corridor, &mut distance, tile, spacing);
+                }
+
+                for n in level.get_neighbours(tile, 1) {
+                    if !visited[n.0][n.1] && is_corridor(n) {
+                        visited[n.0][n.1] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+    }
+
+    lights
+}
+
+/// lowers the stored distance of all corridor tiles closer than `spacing` to the new light.
+fn mark_surroundings(
+    level: &Level,
+    is_corridor: &dyn Fn((usize, usize)) -> bool,
+    distance: &mut [Vec<usize>],
+    light: (usize, usize),
+    spacing: usize,
+) {
+    let mut queue = VecDeque::new();
+    distance[light.0][light.1] = 0;
+    queue.push_back(light);
+
+    while let Some(tile) = queue.pop_front() {
+        let next = distance[tile.0][tile.1] + 1;
+        if next >= spacing {
+            continue;
+        }
+
+        for n in level.get_neighbours(tile, 1) {
+            if is_corridor(n) && distance[n.0][n.1] > next {
+                distance[n.0][n.1] = next;
+                queue.push_back(n);
+            }
+        }
+    }
+}
+
+/// which of the lights cast shadows, given for each of them whether the player can see it and
+/// how far away it is. Visible lights get them first, then the closest ones, until `budget` is
+/// used up.
+pub fn shadow_casters(lights: &[(bool, f32)], budget: usize) -> Vec<bool> {
+    let mut order = (0..lights.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let key = |i: usize| (!lights[i].0, lights[i].1);
+        key(a).partial_cmp(&key(b)).unwrap()
+    });
+
+    let mut casts = vec![false; lights.len()];
+    for &index in order.iter().take(budget) {
+        casts[index] = true;
+    }
+    casts
+}
+
+/// a broken light that randomly switches on and off. It has its own random numbers, so it
+/// flickers the same way every time the level is played with the same seed.
+pub struct FlickeringLight {
+    handle: Handle<Node>,
+    rng: StdRng,
+    /// seconds until the light toggles again
+    timer: f32,
+    /// cleared when the light was switched off
+    enabled: bool,
+}
+
+impl FlickeringLight {
+    pub fn new(handle: Handle<Node>, seed: u64) -> Self {
+        Self {
+            handle,
+            rng: StdRng::seed_from_u64(seed),
+            timer: 0.0,
+            enabled: true,
+        }
+    }
+
+    pub fn handle(&self) -> Handle<Node> {
+        self.handle
+    }
+
+    /// a disabled light stays dark instead of flickering.
+    pub fn set_enabled(&mut self, scene: &mut Scene, enabled: bool) {
+        self.enabled = enabled;
+        self.timer = 0.0;
+        scene.graph[self.handle].set_visibility(enabled);
+    }
+
+    pub fn update(&mut self, scene: &mut Scene, dt: f32) {
+        if !self.enabled {
+            return;
+        }
+
+        self.timer -= dt;
+        if self.timer > 0.0 {
+            return;
+        }
+
+        let light = &mut scene.graph[self.handle];
+        let visible = !light.visibility();
+        light.set_visibility(visible);
+
+        // short blackouts, longer but unsteady phases of light
+        self.timer = if visible {
+            self.rng.gen_range(0.05, 1.5)
+        } else {
+            self.rng.gen_range(0.03, 0.25)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> LightingOptions {
+        LightingOptions {
+            corridor_spacing: 3,
+            corridor_radius: 1.0,
+            room_tiles_per_light: 4,
+            room_radius: 4.0,
+            max_shadow_casters: 2,
+            dark_room_chance: 0.0,
+            emergency_chance: 0.0,
+            broken_chance: 0.0,
+        }
+    }
+
+    /// a room with a corridor leaving it through a door
+    fn room_and_corridor() -> Level {
+        Level::parse(
+            "###########
+             #...#######
+             #...+,,,,,#
+             #...#####,#
+             #########,#
+             #########,#
+             ###########",
+        )
+    }
+
+    #[test]
+    fn corridor_lights_are_spaced_along_the_path() {
+        let level = Level::parse(
+            "############
+             #,,,,,,,,,,#
+             ############",
+        );
+
+        let lights = corridor_light_tiles(&level, 3);
+        assert_eq!(lights, vec![(1, 1), (4, 1), (7, 1), (10, 1)]);
+    }
+
+    #[test]
+    fn corridor_lights_are_spaced_around_corners() {
+        let level = room_and_corridor();
+
+        let lights = corridor_light_tiles(&level, 3);
+        for (i, a) in lights.iter().enumerate() {
+            for b in &lights[i + 1..] {
+                let steps = (a.0 as i32 - b.0 as i32).abs() + (a.1 as i32 - b.1 as i32).abs();
+                assert!(steps >= 3, "{:?} and {:?} are too close", a, b);
+            }
+        }
+        assert_eq!(lights.len(), 3);
+    }
+
+    #[test]
+    fn no_corridor_lights_on_doors() {
+        let level = Level::parse(
+            "#########
+             #,,,+,,,#
+             #########",
+        );
+
+        let lights = corridor_light_tiles(&level, 3);
+        assert!(lights
+            .iter()
+            .all(|&(x, y)| level.map[x][y].typ == FieldType::Corridor));
+        // the door still counts for the distance
+        assert_eq!(lights, vec![(1, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn bigger_rooms_get_more_lights() {
+        let small = Level::parse(
+            "####
+             #..#
+             #..#
+             ####",
+        );
+        let big = Level::parse(
+            "##########
+             #........#
+             #........#
+             #........#
+             #........#
+             ##########",
+        );
+
+        assert_eq!(room_light_positions(&small.rooms[0], 4).len(), 1);
+        assert_eq!(room_light_positions(&big.rooms[0], 4).len(), 8);
+        // at least one, even for rooms smaller than `room_tiles_per_light`
+        assert_eq!(room_light_positions(&small.rooms[0], 100).len(), 1);
+    }
+
+    #[test]
+    fn room_lights_stay_inside_the_room() {
+        let level = Level::parse(
+            "##########
+             #........#
+             #........#
+             #........#
+             #........#
+             ##########",
+        );
+
+        for (x, y) in room_light_positions(&level.rooms[0], 4) {
+            assert!(x > 0.5 && x < 8.5 && y > 0.5 && y < 4.5, "{:?}", (x, y));
+        }
+    }
+
+    #[test]
+    fn dark_rooms_get_no_lights() {
+        let level = room_and_corridor();
+        let options = LightingOptions {
+            dark_room_chance: 1.0,
+            ..options()
+        };
+
+        let lights = plan_lights(&level, &options, &mut StdRng::seed_from_u64(1));
+        assert!(!lights.is_empty());
+        assert!(lights.iter().all(|light| light.room.is_none()));
+    }
+
+    #[test]
+    fn roles_come_from_the_options() {
+        let level = room_and_corridor();
+        let options = LightingOptions {
+            emergency_chance: 1.0,
+            ..options()
+        };
+
+        let lights = plan_lights(&level, &options, &mut StdRng::seed_from_u64(1));
+        assert!(lights
+            .iter()
+            .all(|light| light.role == LightRole::Emergency));
+    }
+
+    #[test]
+    fn all_lights_of_a_room_share_their_role() {
+        let level = Level::parse(
+            "##########
+             #........#
+             #........#
+             #........#
+             #........#
+             ##########",
+        );
+        let options = LightingOptions {
+            broken_chance: 0.5,
+            ..options()
+        };
+
+        for seed in 0..20 {
+            let lights = plan_lights(&level, &options, &mut StdRng::seed_from_u64(seed));
+            assert!(lights.windows(2).all(|pair| pair[0].role == pair[1].role));
+        }
+    }
+
+    #[test]
+    fn shadows_go_to_visible_lights_first() {
+        let lights = [(false, 1.0), (true, 5.0), (false, 2.0), (true, 3.0)];
+
+        assert_eq!(shadow_casters(&lights, 2), vec![false, true, false, true]);
+        assert_eq!(shadow_casters(&lights, 3), vec![true, true, false, true]);
+    }
+
+    #[test]
+    fn shadows_stay_within_the_budget() {
+        let lights = [(true, 1.0); 10];
+
+        assert_eq!(shadow_casters(&lights, 6).iter().filter(|&&c| c).count(), 6);
+        assert_eq!(shadow_casters(&lights[..3], 6), vec![true; 3]);
+        assert_eq!(shadow_casters(&lights, 0), vec![false; 10]);
+    }
+}
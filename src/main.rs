@@ -1,42 +1,93 @@
-#![feature(cmp_min_max_by)]
-
 extern crate rg3d;
 
-use std::cmp::{max_by, min_by};
 use std::time::Instant;
 
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use rg3d::engine::resource_manager::TextureImportOptions;
 use rg3d::gui::message::MessageDirection;
 use rg3d::renderer::{QualitySettings, ShadowMapPrecision};
 use rg3d::resource::texture::{TextureMagnificationFilter, TextureMinificationFilter};
-use rg3d::scene::light::{BaseLightBuilder, PointLightBuilder, SpotLightBuilder};
+use rg3d::scene::light::{BaseLightBuilder, SpotLightBuilder};
 use rg3d::scene::Line;
 use rg3d::{
     core::{color::Color, pool::Handle},
     engine::resource_manager::ResourceManager,
-    event::{DeviceEvent, ElementState, Event, VirtualKeyCode, WindowEvent},
+    event::{DeviceEvent, ElementState, Event, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    gui::{message::TextMessage, node::StubNode, text::TextBuilder, widget::WidgetBuilder},
+    gui::{
+        border::BorderBuilder,
+        brush::Brush,
+        message::{TextMessage, WidgetMessage},
+        node::StubNode,
+        text::TextBuilder,
+        widget::WidgetBuilder,
+        HorizontalAlignment, Thickness,
+    },
     scene::{
         base::BaseBuilder, camera::CameraBuilder, node::Node, transform::TransformBuilder, Scene,
     },
     utils::translate_event,
 };
 
-use crate::level_generator::{FieldType, Level, RoomOptions};
-use crate::player::Player;
-use crate::sound::{add_air_vent_sound, load_footstep_sounds, play_footstep, start_ambient_sound};
+use crate::audio::AudioManager;
+use crate::character_controller::CharacterController;
+use crate::clock::GameClock;
+use crate::flashlight::Flashlight;
+use crate::footsteps::{FootstepSounds, Surface};
+use crate::head_bob::HeadBob;
+use crate::input::Action;
+use crate::interaction::{Highlight, Outcome};
+use crate::inventory::{InventoryEvent, Item};
+use crate::lean::Lean;
+use crate::level_config::LevelConfig;
+use crate::menu::SettingsMenu;
+use crate::mouse_look::MouseLook;
+use crate::movement::ground_directions;
+use crate::occlusion::SoundOcclusion;
+use crate::player::PlayerState;
+use crate::replay::{Recorder, Replay, TickInput};
+use crate::reverb::RoomReverb;
+use crate::scene_builder::SceneAssets;
+use crate::settings::Settings;
+use crate::simulation::Simulation;
+use crate::sound::{
+    load_hurt_sound, play_hurt, start_ambient_sound, start_breathing_sound, update_breathing,
+};
+use crate::world::World;
 use rg3d::futures::executor::block_on;
-use rg3d::physics::na::{UnitQuaternion, Vector3};
-use rg3d::sound::context::Context;
-use std::borrow::BorrowMut;
-use std::sync::{Arc, Mutex};
-
+use rg3d::physics::na::{UnitQuaternion, Vector2, Vector3};
+
+mod audio;
+mod character_controller;
+mod clock;
+mod collision;
+mod flashlight;
+mod footsteps;
+mod head_bob;
+mod input;
+mod interaction;
+mod inventory;
+mod lean;
+mod level_config;
 mod level_generator;
+mod lighting;
+mod menu;
+mod mouse_look;
+mod movement;
+mod occlusion;
+mod oxygen;
 mod player;
+mod props;
+mod replay;
+mod reverb;
+mod ron_file;
+mod scene_builder;
+mod settings;
+mod simulation;
 mod sound;
+mod theme;
+mod visibility;
+mod world;
 
 // Create our own engine type aliases. These specializations are needed
 // because engine provides a way to extend UI with custom nodes and messages.
@@ -48,28 +99,78 @@ fn create_ui(ctx: &mut BuildContext) -> Handle<UiNode> {
     TextBuilder::new(WidgetBuilder::new()).build(ctx)
 }
 
+/// the text below the crosshair telling what the player is looking at
+fn create_prompt(ctx: &mut BuildContext) -> Handle<UiNode> {
+    TextBuilder::new(WidgetBuilder::new().with_width(PROMPT_WIDTH))
+        .with_horizontal_text_alignment(HorizontalAlignment::Center)
+        .build(ctx)
+}
+
+/// a red tint over the whole screen that flashes when the player gets hurt
+fn create_damage_overlay(ctx: &mut BuildContext, width: u32, height: u32) -> Handle<UiNode> {
+    BorderBuilder::new(
+        WidgetBuilder::new()
+            .with_width(width as f32)
+            .with_height(height as f32)
+            .with_background(damage_overlay_brush(0.0)),
+    )
+    .with_stroke_thickness(Thickness::uniform(0.0))
+    .build(ctx)
+}
+
+/// `hurt` is `Player::hurt`
+fn damage_overlay_brush(hurt: f32) -> Brush {
+    Brush::Solid(Color::from_rgba(150, 0, 0, (hurt * 140.0) as u8))
+}
+
+/// the settings menu, see `SettingsMenu`
+fn create_menu(ctx: &mut BuildContext) -> Handle<UiNode> {
+    TextBuilder::new(WidgetBuilder::new().with_desired_position(Vector2::new(300.0, 150.0)))
+        .build(ctx)
+}
+
+const PROMPT_WIDTH: f32 = 600.0;
+
+/// keeps the prompt centered a bit below the middle of the window.
+fn prompt_position(width: u32, height: u32) -> Vector2<f32> {
+    Vector2::new(
+        (width as f32 - PROMPT_WIDTH) / 2.0,
+        height as f32 / 2.0 + 40.0,
+    )
+}
+
+/// seconds a terminal message stays on screen
+const MESSAGE_DURATION: f32 = 6.0;
+/// seconds a change of the inventory stays on screen
+const EVENT_DURATION: f32 = 2.0;
+
+/// freezes the game and frees the cursor, or the other way around.
+fn set_paused(engine: &mut GameEngine, paused: bool) {
+    let window = engine.get_window();
+    // not every platform can grab the cursor, the game works without it
+    let _ = window.set_cursor_grab(!paused);
+    window.set_cursor_visible(paused);
+    engine.sound_context.lock().unwrap().pause(paused);
+}
+
 struct GameScene {
-    player: Player,
     scene: Scene,
+    world: World,
+    controller: CharacterController,
     camera_handle: Handle<Node>,
     flash_light_handle: Handle<Node>,
 }
 
-fn create_point_light(radius: f32) -> Node {
-    let point_light = PointLightBuilder::new(BaseLightBuilder::new(BaseBuilder::new()));
-
-    point_light.with_radius(radius).build_node()
-}
-
 fn create_flash_light(resource_manager: ResourceManager) -> Node {
     let cookie = resource_manager.request_texture("assets/flashlight_cookie.png");
 
+    let (r, g, b) = Flashlight::COLOR;
     let spot_light = SpotLightBuilder::new(
-        BaseLightBuilder::new(BaseBuilder::new()).with_color(Color::from_rgba(232, 226, 185, 255)),
+        BaseLightBuilder::new(BaseBuilder::new()).with_color(Color::opaque(r, g, b)),
     );
 
     spot_light
-        .with_distance(6.0)
+        .with_distance(Flashlight::DISTANCE)
         .with_hotspot_cone_angle(60.0f32.to_radians())
         .with_falloff_angle_delta(12.0f32.to_radians())
         .with_shadow_bias(0.0005)
@@ -77,237 +178,11 @@ fn create_flash_light(resource_manager: ResourceManager) -> Node {
         .build_node()
 }
 
-async fn add_corners(level: &mut Level, scene: &mut Scene, resource_manager: &ResourceManager) {
-    let wall_inner_corner_resource = resource_manager
-        .request_model("assets/wall_inner_corner.fbx")
-        .await
-        .unwrap();
-
-    let wall_outer_corner_resource = resource_manager
-        .request_model("assets/wall_outer_corner.fbx")
-        .await
-        .unwrap();
-
-    for x in 0..level.map.len() {
-        for y in 0..level.map[0].len() {
-            if level.map[x][y].typ == FieldType::Empty {
-                // add outer corners
-                let neighbours = level
-                    .get_neighbours((x, y), 1)
-                    .into_iter()
-                    .filter(|&(x, y)| level.map[x][y].typ != FieldType::Empty)
-                    .collect::<Vec<_>>();
-
-                let mut add_corner = |rotation: f32| {
-                    let corner_handle = wall_outer_corner_resource.instantiate_geometry(scene);
-                    scene.graph[corner_handle]
-                        .local_transform_mut()
-                        .set_rotation(UnitQuaternion::from_axis_angle(
-                            &Vector3::y_axis(),
-                            rotation.to_radians(),
-                        ))
-                        .offset(Vector3::new(x as f32, 0.0, y as f32));
-                };
-
-                if neighbours.iter().any(|&(n_x, _)| n_x < x)
-                    && neighbours.iter().any(|&(_, n_y)| n_y < y)
-                {
-                    add_corner(0.0);
-                    level.map[x - 1][y].walls.right_up = true;
-                    level.map[x][y - 1].walls.down_left = true;
-                }
-
-                if neighbours.iter().any(|&(n_x, _)| n_x < x)
-                    && neighbours.iter().any(|&(_, n_y)| n_y > y)
-                {
-                    add_corner(90.0);
-                    level.map[x - 1][y].walls.right_down = true;
-                    level.map[x][y + 1].walls.up_left = true;
-                }
-
-                if neighbours.iter().any(|&(n_x, _)| n_x > x)
-                    && neighbours.iter().any(|&(_, n_y)| n_y < y)
-                {
-                    add_corner(-90.0);
-                    level.map[x + 1][y].walls.left_up = true;
-                    level.map[x][y - 1].walls.down_right = true;
-                }
-
-                if neighbours.iter().any(|&(n_x, _)| n_x > x)
-                    && neighbours.iter().any(|&(_, n_y)| n_y > y)
-                {
-                    add_corner(180.0);
-                    level.map[x + 1][y].walls.left_down = true;
-                    level.map[x][y + 1].walls.up_right = true;
-                }
-
-                continue;
-            }
-
-            // add inner corners
-            let neighbours = level
-                .get_neighbours((x, y), 1)
-                .into_iter()
-                .filter(|&(x, y)| level.map[x][y].typ == FieldType::Empty)
-                .collect::<Vec<_>>();
-
-            let mut add_corner = |rotation: f32| {
-                let corner_handle = wall_inner_corner_resource.instantiate_geometry(scene);
-                scene.graph[corner_handle]
-                    .local_transform_mut()
-                    .set_rotation(UnitQuaternion::from_axis_angle(
-                        &Vector3::y_axis(),
-                        rotation.to_radians(),
-                    ))
-                    .offset(Vector3::new(x as f32, 0.0, y as f32));
-            };
-
-            if neighbours.iter().any(|&(n_x, _)| n_x < x)
-                && neighbours.iter().any(|&(_, n_y)| n_y < y)
-            {
-                level.map[x][y].walls.up_left = true;
-                level.map[x][y].walls.left_up = true;
-                add_corner(0.0);
-            }
-
-            if neighbours.iter().any(|&(n_x, _)| n_x > x)
-                && neighbours.iter().any(|&(_, n_y)| n_y < y)
-            {
-                level.map[x][y].walls.up_right = true;
-                level.map[x][y].walls.right_up = true;
-                add_corner(-90.0);
-            }
-
-            if neighbours.iter().any(|&(n_x, _)| n_x < x)
-                && neighbours.iter().any(|&(_, n_y)| n_y > y)
-            {
-                level.map[x][y].walls.down_left = true;
-                level.map[x][y].walls.left_down = true;
-                add_corner(90.0);
-            }
-
-            if neighbours.iter().any(|&(n_x, _)| n_x > x)
-                && neighbours.iter().any(|&(_, n_y)| n_y > y)
-            {
-                level.map[x][y].walls.down_right = true;
-                level.map[x][y].walls.right_down = true;
-                add_corner(180.0);
-            }
-        }
-    }
-}
-
-async fn add_rest(level: &mut Level, scene: &mut Scene, resource_manager: &ResourceManager) {
-    let wall_resource = resource_manager
-        .request_model("assets/wall.fbx")
-        .await
-        .unwrap();
-
-    let floor_resource = resource_manager
-        .request_model("assets/floor.fbx")
-        .await
-        .unwrap();
-
-    let corridor_resource = resource_manager
-        .request_model("assets/corridor.fbx")
-        .await
-        .unwrap();
-
-    let mut tile_count = 0;
-    for x in 0..level.map.len() {
-        for y in 0..level.map[0].len() {
-            if level.map[x][y].typ == FieldType::Empty {
-                continue;
-            }
-
-            tile_count += 1;
-
-            // create floor
-            let floor_handle = match level.map[x][y].typ {
-                FieldType::Floor => floor_resource.instantiate_geometry(scene),
-                FieldType::Corridor => corridor_resource.instantiate_geometry(scene),
-                FieldType::Door => corridor_resource.instantiate_geometry(scene),
-                _ => floor_resource.instantiate_geometry(scene), // should be something else...
-            };
-
-            scene.graph[floor_handle]
-                .local_transform_mut()
-                .offset(Vector3::new(x as f32, 0.0, y as f32));
-
-            // add light to floors
-            if level.map[x][y].typ == FieldType::Corridor && tile_count % 3 == 0 {
-                let handle = scene.graph.add_node(create_point_light(1.0));
-                scene.graph[handle]
-                    .local_transform_mut()
-                    .offset(Vector3::new(x as f32, 0.3, y as f32));
-            }
-
-            // fill in missing walls
-            let add_wall = |scene: &mut Scene, rotation: f32, offset_x: f32, offset_y: f32| {
-                let wall_handle = wall_resource.instantiate_geometry(scene);
-                scene.graph[wall_handle]
-                    .local_transform_mut()
-                    .set_rotation(UnitQuaternion::from_axis_angle(
-                        &Vector3::y_axis(),
-                        rotation.to_radians(),
-                    ))
-                    .offset(Vector3::new(x as f32 + offset_x, 0.0, y as f32 + offset_y));
-            };
-
-            let neighbours = level
-                .get_neighbours((x, y), 1)
-                .into_iter()
-                .filter(|(x, y)| level.map[*x][*y].typ == FieldType::Empty)
-                .collect::<Vec<_>>();
-
-            for n in neighbours {
-                let mut walls = &mut level.map[x][y].walls;
-                if n.0 < x {
-                    if !walls.left_up {
-                        add_wall(scene, 90.0, 0.0, -0.5);
-                        walls.left_up = true;
-                    }
-                    if !walls.left_down {
-                        add_wall(scene, 90.0, 0.0, 0.0);
-                        walls.left_down = true;
-                    }
-                }
-                if n.0 > x {
-                    if !walls.right_up {
-                        add_wall(scene, -90.0, 0.0, 0.0);
-                        walls.right_up = true;
-                    }
-                    if !walls.right_down {
-                        add_wall(scene, -90.0, 0.0, 0.5);
-                        walls.right_down = true;
-                    }
-                }
-                if n.1 < y {
-                    if !walls.up_left {
-                        add_wall(scene, 0.0, 0.0, 0.0);
-                        walls.up_left = true;
-                    }
-                    if !walls.up_right {
-                        add_wall(scene, 0.0, 0.5, 0.0);
-                        walls.up_right = true;
-                    }
-                }
-                if n.1 > y {
-                    if !walls.down_left {
-                        add_wall(scene, 180.0, -0.5, 0.0);
-                        walls.down_left = true;
-                    }
-                    if !walls.down_right {
-                        add_wall(scene, 180.0, 0.0, 0.0);
-                        walls.down_right = true;
-                    }
-                }
-            }
-        }
-    }
-}
-
-async fn create_scene(resource_manager: ResourceManager, ctx: Arc<Mutex<Context>>) -> GameScene {
+async fn create_scene(
+    resource_manager: ResourceManager,
+    audio: &mut AudioManager,
+    seed: u64,
+) -> GameScene {
     let mut scene = Scene::new();
 
     resource_manager.state().set_textures_import_options(
@@ -316,126 +191,22 @@ async fn create_scene(resource_manager: ResourceManager, ctx: Arc<Mutex<Context>
             .with_magnification_filter(TextureMagnificationFilter::Nearest),
     );
 
-    // create level
-    let mut level = Level::create_dungeon(
-        23,
-        39,
-        RoomOptions {
-            max_rooms: 10,
-            max_attempts: 125,
-            min_size: 4,
-            max_size: 10,
-        },
-        FieldType::Floor,
-    );
-
-    add_corners(&mut level, &mut scene, &resource_manager).await;
-    add_rest(&mut level, &mut scene, &resource_manager).await;
-
-    let pl = create_point_light(4.0);
-
-    let air_vent = resource_manager
-        .request_model("assets/air_vent.fbx")
-        .await
-        .unwrap();
-
-    let oxygen_tank = resource_manager
-        .request_model("assets/oxygen.fbx")
-        .await
-        .unwrap();
-
-    let mut rng = thread_rng();
-
-    for room in &mut level.rooms {
-        // add lights
-        room.sort();
-        let pos = room[room.len() / 2];
-
-        let point_light = scene.graph.add_node(pl.raw_copy());
-
-        scene.graph[point_light]
-            .local_transform_mut()
-            .set_position(Vector3::new(pos.0 as f32, 2.0, pos.1 as f32));
-
-        // add vents
-        let (min_x, min_y) = room[0];
-        let (max_x, max_y) = room[room.len() - 1];
-        let edges = room
-            .clone()
-            .into_iter()
-            .filter(|&(x, y)| x == min_x || x == max_x || y == min_y || y == max_y)
-            .collect::<Vec<_>>();
-
-        'attempt: loop {
-            let pos = edges.choose(&mut rng).unwrap();
-
-            let walls = &level.map[pos.0][pos.1].walls;
-
-            let sound_offset: (f32, f32);
-
-            let rot: f32;
-            if walls.up_left {
-                rot = 0.0;
-                sound_offset = (0.0, -0.5);
-            } else if walls.right_up {
-                rot = 270.0;
-                sound_offset = (0.5, 0.0);
-            } else if walls.down_left {
-                rot = 180.0;
-                sound_offset = (0.0, 0.5);
-            } else if walls.left_up {
-                rot = 90.0;
-                sound_offset = (-0.5, 0.0);
-            } else {
-                // no wall!
-                continue 'attempt;
-            }
+    let assets = SceneAssets::load(&resource_manager).await;
 
-            let handle = air_vent.instantiate_geometry(&mut scene);
-            scene.graph[handle]
-                .local_transform_mut()
-                .offset(Vector3::new(pos.0 as f32, 0.0, pos.1 as f32))
-                .set_rotation(UnitQuaternion::from_axis_angle(
-                    &Vector3::y_axis(),
-                    rot.to_radians(),
-                ));
-
-            add_air_vent_sound(
-                ctx.clone(),
-                &resource_manager,
-                pos.0 as f32 + sound_offset.0,
-                pos.1 as f32 + sound_offset.1,
-            )
-            .await;
-
-            break 'attempt;
-        }
+    // create level
+    let mut world = World::new(seed, LevelConfig::load("assets/level.ron"), assets);
 
-        let oxygen_tank_pos = room.choose(&mut rng).unwrap();
-
-        let handle = oxygen_tank.instantiate_geometry(&mut scene);
-        scene.graph[handle]
-            .local_transform_mut()
-            .offset(Vector3::new(
-                oxygen_tank_pos.0 as f32,
-                0.0,
-                oxygen_tank_pos.1 as f32,
-            ))
-            .set_rotation(UnitQuaternion::from_axis_angle(
-                &Vector3::y_axis(),
-                [0.0f32, 90.0, 180.0, 270.0]
-                    .choose(&mut rng)
-                    .unwrap()
-                    .to_radians(),
-            ));
-    }
+    let spawn_point = world.spawn_point(&mut scene, audio);
+    world.update(&mut scene, audio, spawn_point);
 
     let environment_texture = resource_manager.request_texture("assets/environment.dds");
 
+    let controller = CharacterController::new(&mut scene, spawn_point);
+
     let camera = CameraBuilder::new(
         BaseBuilder::new().with_local_transform(
             TransformBuilder::new()
-                .with_local_position(Vector3::new(7.0, 0.5, 7.0))
+                .with_local_position(Vector3::new(0.0, controller.eye_height(), 0.0))
                 .build(),
         ),
     )
@@ -443,8 +214,7 @@ async fn create_scene(resource_manager: ResourceManager, ctx: Arc<Mutex<Context>
     .build();
 
     let camera_handle = scene.graph.add_node(Node::Camera(camera));
-
-    let camera_pos = scene.graph[camera_handle].global_position();
+    scene.graph.link_nodes(camera_handle, controller.pivot());
 
     let flash_light_handle = scene
         .graph
@@ -456,31 +226,20 @@ async fn create_scene(resource_manager: ResourceManager, ctx: Arc<Mutex<Context>
             &Vector3::x_axis(),
             -90.0f32.to_radians(),
         ))
-        .set_position(camera_pos + Vector3::new(-0.3, -0.2, 0.0));
+        .set_position(Vector3::new(-0.3, -0.2, 0.0));
     scene.graph[flash_light_handle].set_visibility(false);
 
     scene.graph.link_nodes(flash_light_handle, camera_handle);
 
-    start_ambient_sound(ctx.clone(), resource_manager.clone()).await;
-
     GameScene {
-        player: Player::default(),
         scene,
+        world,
+        controller,
         camera_handle,
         flash_light_handle,
     }
 }
 
-struct InputController {
-    move_left: bool,
-    move_right: bool,
-    move_forward: bool,
-    move_backward: bool,
-    run: bool,
-    jump: bool,
-    crouch: bool,
-}
-
 fn main() {
     let event_loop = EventLoop::new();
 
@@ -514,9 +273,29 @@ fn main() {
     }
 
     engine.resource_manager.state().set_textures_path("assets");
-    engine.get_window().set_cursor_visible(false);
-
+    set_paused(&mut engine, false);
+
+    let damage_overlay = {
+        let size = engine.get_window().inner_size();
+        create_damage_overlay(
+            &mut engine.user_interface.build_ctx(),
+            size.width,
+            size.height,
+        )
+    };
     let debug_text = create_ui(&mut engine.user_interface.build_ctx());
+    let prompt = create_prompt(&mut engine.user_interface.build_ctx());
+    let menu_text = create_menu(&mut engine.user_interface.build_ctx());
+    {
+        let size = engine.get_window().inner_size();
+        engine
+            .user_interface
+            .send_message(WidgetMessage::desired_position(
+                prompt,
+                MessageDirection::ToWidget,
+                prompt_position(size.width, size.height),
+            ));
+    }
 
     // engine
     //     .sound_context
@@ -526,46 +305,181 @@ fn main() {
     //         HrirSphere::from_file("assets/IRC_1005_C.bin", context::SAMPLE_RATE).unwrap(),
     //     )));
 
+    // `--record <file>` saves the input of every tick, `--replay <file>` plays it back
+    let args = std::env::args().collect::<Vec<_>>();
+    let arg = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+    let mut replay = arg("--replay").map(|path| Replay::load(&path));
+
+    let seed = replay
+        .as_ref()
+        .map_or_else(|| thread_rng().gen(), Replay::seed);
+
+    let mut recorder = arg("--record").map(|path| Recorder::new(path, seed));
+
+    let mut settings = Settings::load();
+    let mut audio = AudioManager::new(engine.sound_context.clone(), &settings.audio);
+
     let GameScene {
-        mut player,
         scene,
-        camera_handle,
+        mut world,
+        mut controller,
+        mut camera_handle,
         flash_light_handle,
     } = block_on(create_scene(
         engine.resource_manager.clone(),
-        engine.sound_context.clone(),
+        &mut audio,
+        seed,
     ));
 
-    let scene_handle = engine.scenes.add(scene);
+    let mut scene_handle = engine.scenes.add(scene);
 
-    let foot_step = block_on(load_footstep_sounds(&mut engine.resource_manager));
+    block_on(start_ambient_sound(
+        &mut audio,
+        engine.resource_manager.clone(),
+    ));
+    let footsteps = block_on(FootstepSounds::load(
+        &engine.resource_manager,
+        "assets/footsteps.ron",
+    ));
+    let hurt = block_on(load_hurt_sound(&mut engine.resource_manager));
+    let breathing = block_on(start_breathing_sound(
+        &mut audio,
+        engine.resource_manager.clone(),
+    ));
 
     engine.renderer.set_ambient_color(Color::opaque(20, 20, 20));
 
+    let mut menu = SettingsMenu::default();
+    let mut mouse_look = MouseLook::default();
+    let mut head_bob = HeadBob::default();
+    let mut lean = Lean::default();
+
     let clock = Instant::now();
     let fixed_timestep = 1.0 / 60.0;
     let mut elapsed_time = 0.0;
+    let mut game_clock = GameClock::default();
 
-    let mut camera_x = 0.0f32.to_radians();
-    let mut camera_y = 0.0f32.to_radians();
-
-    let mut input_controller = InputController {
-        move_left: false,
-        move_right: false,
-        move_forward: false,
-        move_backward: false,
-        run: false,
-        jump: false,
-        crouch: false,
-    };
+    let mut simulation = Simulation::default();
+    let mut room_reverb = RoomReverb::default();
+    let mut sound_occlusion = SoundOcclusion::default();
+    // what happened on the controls since the last tick
+    let mut pending_input = TickInput::default();
+    let mut pending_mouse = (0.0, 0.0);
+
+    let mut flashlight = Flashlight::new(flash_light_handle);
+
+    let mut highlight = Highlight::default();
+    // terminal text or inventory change shown instead of the prompt, and until when
+    let mut notice: Option<(String, f32)> = None;
+    // set on the game over screen, the game starts over with this seed on the next tick
+    let mut restart_seed: Option<u64> = None;
+    // the simulation stands still while the pause menu is open
+    let mut paused = false;
 
     event_loop.run(move |event, _, control_flow| {
         match event {
             Event::MainEventsCleared => {
+                if paused {
+                    // no catching up on the missed ticks after resuming
+                    elapsed_time = clock.elapsed().as_secs_f32();
+
+                    let bindings = &settings.key_bindings;
+                    engine.user_interface.send_message(TextMessage::text(
+                        prompt,
+                        MessageDirection::ToWidget,
+                        format!(
+                            "PAUSED\n\n[{}] resume    [{}] settings    [{}] quit",
+                            bindings.name(Action::Pause),
+                            bindings.name(Action::Menu),
+                            bindings.name(Action::Quit)
+                        ),
+                    ));
+                    engine.user_interface.send_message(TextMessage::text(
+                        menu_text,
+                        MessageDirection::ToWidget,
+                        menu.text(&settings),
+                    ));
+
+                    // only the UI keeps running, `engine.update` would step the physics
+                    let size = engine.get_window().inner_size();
+                    engine.user_interface.update(
+                        Vector2::new(size.width as f32, size.height as f32),
+                        fixed_timestep,
+                    );
+                }
+
                 let mut dt = clock.elapsed().as_secs_f32() - elapsed_time;
                 while dt >= fixed_timestep {
                     dt -= fixed_timestep;
                     elapsed_time += fixed_timestep;
+                    game_clock.advance(fixed_timestep);
+
+                    let input = match &mut replay {
+                        Some(replay) => match replay.next_tick() {
+                            Some(input) => input.clone(),
+                            None => {
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                        },
+                        None => {
+                            let mut input = std::mem::take(&mut pending_input);
+                            input.look = mouse_look.update(
+                                &settings.look,
+                                std::mem::take(&mut pending_mouse),
+                                fixed_timestep,
+                            );
+                            input
+                        }
+                    };
+
+                    if let Some(seed) = restart_seed.take() {
+                        if let Some(recorder) = &mut recorder {
+                            recorder.start_over(seed);
+                        }
+
+                        world.clear(&mut engine.scenes[scene_handle], &mut audio);
+                        engine.scenes.remove(scene_handle);
+
+                        let game_scene = block_on(create_scene(
+                            engine.resource_manager.clone(),
+                            &mut audio,
+                            seed,
+                        ));
+                        simulation = Simulation::default();
+                        world = game_scene.world;
+                        controller = game_scene.controller;
+                        camera_handle = game_scene.camera_handle;
+                        flashlight = Flashlight::new(game_scene.flash_light_handle);
+                        scene_handle = engine.scenes.add(game_scene.scene);
+
+                        head_bob = HeadBob::default();
+                        lean = Lean::default();
+                        highlight = Highlight::default();
+                        notice = None;
+                    }
+
+                    if let Some(recorder) = &mut recorder {
+                        recorder.record(&input);
+                    }
+
+                    for action in simulation.apply(&input) {
+                        match action {
+                            Action::ToggleFlashlight => flashlight.toggle(),
+                            Action::Restart => restart_seed = Some(world.seed()),
+                            // the next game is recorded to a file of its own, a replay ends
+                            // here instead of rolling a seed nobody recorded
+                            Action::NewGame if replay.is_none() => {
+                                restart_seed = Some(thread_rng().gen())
+                            }
+                            _ => (),
+                        }
+                    }
 
                     // ************************
                     // Put your game logic here.
@@ -575,91 +489,269 @@ fn main() {
                     // engine.
                     let scene = &mut engine.scenes[scene_handle];
 
+                    if let Node::Camera(camera) = &mut scene.graph[camera_handle] {
+                        camera.set_fov(settings.look.fov.to_radians());
+                    }
+
                     scene.graph[camera_handle]
                         .local_transform_mut()
                         .set_rotation(
                             UnitQuaternion::from_axis_angle(
                                 &Vector3::y_axis(),
-                                -camera_x.to_radians(),
+                                -simulation.look.0.to_radians(),
                             ) * &UnitQuaternion::from_axis_angle(
                                 &Vector3::x_axis(),
-                                camera_y.to_radians(),
+                                simulation.look.1.to_radians(),
+                            ) * &UnitQuaternion::from_axis_angle(
+                                &Vector3::z_axis(),
+                                lean.roll().to_radians(),
                             ),
                         );
 
-                    let side = scene.graph[camera_handle].side_vector();
-                    let mut back_front = scene.graph[camera_handle].look_vector();
-                    back_front.y = 0.0;
-                    back_front = back_front.try_normalize(0.0).unwrap_or(Vector3::default());
-
-                    let mut offset = Vector3::default();
+                    // the grid gives us the sliding along walls, the physics takes care of
+                    // everything else
+                    let position = controller.position(scene);
+                    let (x, z) = simulation.step(
+                        &world,
+                        (position.x, position.z),
+                        controller.is_on_ground(),
+                        &world.config().oxygen,
+                        fixed_timestep,
+                    );
+                    let player = &mut simulation.player;
+                    let controls = &mut simulation.controls;
 
-                    if input_controller.move_right {
-                        offset -= side;
-                    }
-                    if input_controller.move_left {
-                        offset += side;
+                    update_breathing(&mut audio, breathing, player.is_exhausted());
+                    if player.take_hit() {
+                        play_hurt(&mut audio, hurt.clone(), player.hurt());
                     }
-                    if input_controller.move_forward {
-                        offset += back_front;
+
+                    engine
+                        .user_interface
+                        .send_message(WidgetMessage::background(
+                            damage_overlay,
+                            MessageDirection::ToWidget,
+                            damage_overlay_brush(player.hurt()),
+                        ));
+
+                    controller.update(
+                        scene,
+                        (
+                            (x - position.x) / fixed_timestep,
+                            (z - position.z) / fixed_timestep,
+                        ),
+                        controls.jump && !player.is_dead(),
+                        controls.crouch && !player.is_dead(),
+                    );
+
+                    // steps follow the distance we really cover after sliding along the walls,
+                    // so walking into a wall stays silent
+                    let distance = (x - position.x).hypot(z - position.z);
+                    let on_ground = controller.is_on_ground();
+                    if on_ground
+                        && player.advance_stride(distance, game_clock.now(), &settings.footsteps)
+                    {
+                        if let Some(field) = world.field_type(position) {
+                            footsteps.play(&mut audio, Surface::of(field), player.state());
+                        }
                     }
-                    if input_controller.move_backward {
-                        offset -= back_front;
+
+                    let (sway, bob) = head_bob.update(
+                        &settings.head_bob,
+                        player.stride_phase(),
+                        on_ground && distance > 0.0,
+                        player.state() == PlayerState::Running,
+                        fixed_timestep,
+                    );
+
+                    // peeking around corners, the side vector points to the left
+                    let (side, _) = ground_directions(simulation.look.0);
+                    let lean_direction = if player.state().can_lean() {
+                        controls.lean_right as i32 - controls.lean_left as i32
+                    } else {
+                        0
+                    };
+                    lean.update(
+                        &world,
+                        lean_direction as f32,
+                        (x, z),
+                        (-side.0, -side.1),
+                        fixed_timestep,
+                    );
+
+                    // getting hit shakes the camera for a moment
+                    let shake = (game_clock.now() * 40.0).sin() * player.hurt() * 0.03;
+
+                    // the pivot doesn't turn with the view, so everything sideways has to go
+                    // along the side vector
+                    let sideways = sway + shake - lean.offset();
+                    scene.graph[camera_handle]
+                        .local_transform_mut()
+                        .set_position(Vector3::new(
+                            side.0 * sideways,
+                            controller.eye_height() + player.state().eye_offset() + bob,
+                            side.1 * sideways,
+                        ));
+
+                    let camera = &scene.graph[camera_handle];
+
+                    // update listener
+                    {
+                        let mut ctx = engine.sound_context.lock().unwrap();
+                        let listener = ctx.listener_mut();
+                        listener.set_position(camera.global_position());
+                        listener.set_orientation_rh(camera.look_vector(), camera.up_vector());
                     }
 
-                    if input_controller.move_forward
-                        || input_controller.move_backward
-                        || input_controller.move_left
-                        || input_controller.move_right
+                    // stream in the chunks around the player
                     {
-                        player.walk();
-                    } else {
-                        player.stand()
+                        let position = controller.position(scene);
+                        world.update(scene, &mut audio, position);
                     }
 
-                    if input_controller.run {
-                        player.run();
+                    // the echo follows the room the listener is in, walls muffle what's behind
+                    // them
+                    {
+                        let position = scene.graph[camera_handle].global_position();
+                        room_reverb.update(
+                            &mut audio,
+                            world.reverb(position),
+                            &world.config().reverb,
+                            fixed_timestep,
+                        );
+                        sound_occlusion.update(
+                            &mut audio,
+                            &world,
+                            position,
+                            &world.config().occlusion,
+                            fixed_timestep,
+                        );
                     }
 
-                    if player.should_play_step_sound() {
-                        let mut ctx = engine.sound_context.lock().unwrap();
-                        play_footstep(&mut ctx, foot_step.clone(), &player.walk_state)
+                    {
+                        let position = controller.position(scene);
+                        world.update_lights(scene, position, fixed_timestep);
                     }
 
-                    let speed = if input_controller.run {
-                        Player::SPEED + Player::EXTRA_RUN_SPEED
+                    // whatever the player is looking at gets highlighted and can be used
+                    let (eye, look) = {
+                        let camera = &scene.graph[camera_handle];
+                        (camera.global_position(), camera.look_vector().normalize())
+                    };
+                    let target = if player.is_dead() {
+                        None
                     } else {
-                        Player::SPEED
+                        world.find_target(scene, eye, look, controller.collider())
                     };
 
-                    offset.x *= speed;
-                    offset.z *= speed;
+                    let target_node = target.and_then(|t| world.interactable(t)).map(|i| i.node);
+                    highlight.set(scene, target_node);
+
+                    if controls.interact {
+                        controls.interact = false;
+                        if let Some(target) = target {
+                            let position = controller.position(scene);
+                            match world.interactable(target).and_then(|i| i.item()) {
+                                // leave it where it is, adding it anyway tells the HUD why
+                                Some(item) if !player.inventory.can_add(item) => {
+                                    player.inventory.add(item);
+                                }
+                                _ => match world.interact(scene, target, position) {
+                                    Outcome::PickedUp(item) => {
+                                        player.inventory.add(item);
+                                    }
+                                    Outcome::Message(message) => {
+                                        notice =
+                                            Some((message, game_clock.now() + MESSAGE_DURATION))
+                                    }
+                                    Outcome::Nothing => (),
+                                },
+                            }
+                        }
+                    }
+
+                    if controls.use_tank {
+                        controls.use_tank = false;
+                        player.use_item(Item::OxygenTank, &world.config().oxygen);
+                    }
 
-                    if input_controller.jump {
-                        offset.y += speed;
+                    if controls.drop_tank {
+                        controls.drop_tank = false;
+                        if player.inventory.contains(Item::OxygenTank) {
+                            let position = controller.position(scene);
+                            if world.drop_oxygen_tank(scene, position) {
+                                player.inventory.remove(Item::OxygenTank);
+                            }
+                        }
                     }
-                    if input_controller.crouch {
-                        offset.y -= speed;
+
+                    if controls.insert_battery {
+                        controls.insert_battery = false;
+                        if player.inventory.remove(Item::Battery) {
+                            flashlight.insert_battery();
+                        }
                     }
 
-                    let camera = &mut scene.graph[camera_handle];
+                    if controls.crank && !player.is_dead() {
+                        flashlight.crank(fixed_timestep);
+                    }
 
-                    camera.local_transform_mut().offset(offset);
+                    flashlight.update(scene, fixed_timestep, &world.config().flashlight);
 
-                    // update listener
-                    {
-                        let mut ctx = engine.sound_context.lock().unwrap();
-                        let listener = ctx.listener_mut();
-                        listener.set_position(camera.global_position());
-                        listener.set_orientation_rh(camera.look_vector(), camera.up_vector());
+                    while let Some(event) = player.inventory.poll_event() {
+                        let text = match event {
+                            InventoryEvent::Added(item) => format!("+ {}", item.name()),
+                            InventoryEvent::Removed(item) => format!("- {}", item.name()),
+                            InventoryEvent::Full(item) => {
+                                format!("can't carry another {}", item.name())
+                            }
+                        };
+                        notice = Some((text, game_clock.now() + EVENT_DURATION));
                     }
 
+                    let prompt_text = match &notice {
+                        _ if player.is_dead() => format!(
+                            "You {}.\n\n[{}] try again    [{}] new station",
+                            player.cause_of_death().unwrap_or("died"),
+                            settings.key_bindings.name(Action::Restart),
+                            settings.key_bindings.name(Action::NewGame)
+                        ),
+                        Some((message, until)) if game_clock.now() < *until => message.clone(),
+                        _ => target
+                            .and_then(|t| world.interactable(t))
+                            .map_or(String::new(), |i| {
+                                i.prompt(&settings.key_bindings.name(Action::Interact))
+                            }),
+                    };
+                    engine.user_interface.send_message(TextMessage::text(
+                        prompt,
+                        MessageDirection::ToWidget,
+                        prompt_text,
+                    ));
+                    engine.user_interface.send_message(TextMessage::text(
+                        menu_text,
+                        MessageDirection::ToWidget,
+                        menu.text(&settings),
+                    ));
+
                     let fps = engine.renderer.get_statistics().frames_per_second;
-                    let text = format!(
-                        "FPS: {} \nDraw Calls: {}",
+                    let mut text = format!(
+                        "FPS: {} \nDraw Calls: {}\nSeed: {}\nState: {:?} (noise {:.1})\n\
+                         Health: {:.0}%\nStamina: {:.0}%\nOxygen: {:.0}%\nFlashlight: {:.0}%",
                         fps,
-                        engine.renderer.get_statistics().geometry.draw_calls
+                        engine.renderer.get_statistics().geometry.draw_calls,
+                        world.seed(),
+                        player.state(),
+                        player.state().noise_level(),
+                        player.health() * 100.0,
+                        player.stamina() * 100.0,
+                        player.oxygen() * 100.0,
+                        flashlight.charge() * 100.0
                     );
+                    text += "\nInventory:";
+                    for &(item, count) in player.inventory.items() {
+                        text += &format!(" {} x{},", item.name(), count);
+                    }
 
                     engine.user_interface.send_message(TextMessage::text(
                         debug_text,
@@ -685,6 +777,8 @@ fn main() {
                         color: Color::GREEN,
                     });
 
+                    audio.update(&settings.audio, fixed_timestep);
+
                     engine.update(fixed_timestep);
                 }
 
@@ -707,56 +801,89 @@ fn main() {
                 engine.render(fixed_timestep).unwrap();
             }
             Event::WindowEvent { event, .. } => {
+                let bound = match &event {
+                    WindowEvent::MouseInput { button, state, .. } => {
+                        Some((settings.key_bindings.mouse_actions(*button), *state))
+                    }
+                    // Handle key input events via `WindowEvent`, not via `DeviceEvent` (#32)
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        Some((settings.key_bindings.key_actions(input), input.state))
+                    }
+                    _ => None,
+                };
+
+                // the menu is handled right away, the next tick takes care of the rest. A replay
+                // brings its own.
+                if let Some((actions, state)) = bound {
+                    let pressed = state == ElementState::Pressed;
+                    for action in actions {
+                        if pressed && menu.handle(action, &mut settings) {
+                            continue;
+                        }
+
+                        match action {
+                            Action::Pause if pressed => {
+                                paused = !paused;
+                                set_paused(&mut engine, paused);
+                                continue;
+                            }
+                            Action::Quit if pressed && paused => {
+                                if let Some(recorder) = &mut recorder {
+                                    recorder.save();
+                                }
+                                *control_flow = ControlFlow::Exit;
+                                return;
+                            }
+                            _ => (),
+                        }
+
+                        // while paused only releases get through, so no key gets stuck
+                        if replay.is_none() && (!paused || !pressed) {
+                            pending_input.actions.push((action, pressed));
+                        }
+                    }
+                }
+
                 match event {
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                    WindowEvent::CloseRequested => {
+                        if let Some(recorder) = &mut recorder {
+                            recorder.save();
+                        }
+                        *control_flow = ControlFlow::Exit
+                    }
+                    WindowEvent::Focused(false) => {
+                        // keys released in the background would stay stuck, the next tick lets
+                        // go of everything
+                        if replay.is_none() {
+                            pending_input.focus_lost = true;
+                        }
+                        if !paused {
+                            paused = true;
+                            set_paused(&mut engine, true);
+                        }
+                    }
                     WindowEvent::Resized(size) => {
                         // It is very important to handle Resized event from window, because
                         // renderer knows nothing about window size - it must be notified
                         // directly when window size has changed.
                         engine.renderer.set_frame_size(size.into());
-                    }
-                    WindowEvent::KeyboardInput { input, .. } => {
-                        // Handle key input events via `WindowEvent`, not via `DeviceEvent` (#32)
-                        if let Some(key_code) = input.virtual_keycode {
-                            match key_code {
-                                VirtualKeyCode::A => {
-                                    input_controller.move_left =
-                                        input.state == ElementState::Pressed
-                                }
-                                VirtualKeyCode::D => {
-                                    input_controller.move_right =
-                                        input.state == ElementState::Pressed
-                                }
-                                VirtualKeyCode::W => {
-                                    input_controller.move_forward =
-                                        input.state == ElementState::Pressed
-                                }
-                                VirtualKeyCode::S => {
-                                    input_controller.move_backward =
-                                        input.state == ElementState::Pressed
-                                }
-                                VirtualKeyCode::LShift => {
-                                    input_controller.run = input.state == ElementState::Pressed
-                                }
-                                VirtualKeyCode::Space => {
-                                    input_controller.jump = input.state == ElementState::Pressed
-                                }
-                                VirtualKeyCode::C => {
-                                    input_controller.crouch = input.state == ElementState::Pressed
-                                }
-                                VirtualKeyCode::F => {
-                                    if input.state == ElementState::Released {
-                                        let scene = &mut engine.scenes[scene_handle];
-                                        let flash_light =
-                                            scene.graph[flash_light_handle].borrow_mut();
-                                        let visibility = flash_light.visibility();
-                                        flash_light.set_visibility(!visibility);
-                                    }
-                                }
-                                VirtualKeyCode::Escape => *control_flow = ControlFlow::Exit,
-                                _ => (),
-                            }
-                        }
+                        engine
+                            .user_interface
+                            .send_message(WidgetMessage::desired_position(
+                                prompt,
+                                MessageDirection::ToWidget,
+                                prompt_position(size.width, size.height),
+                            ));
+                        engine.user_interface.send_message(WidgetMessage::width(
+                            damage_overlay,
+                            MessageDirection::ToWidget,
+                            size.width as f32,
+                        ));
+                        engine.user_interface.send_message(WidgetMessage::height(
+                            damage_overlay,
+                            MessageDirection::ToWidget,
+                            size.height as f32,
+                        ));
                     }
                     _ => (),
                 }
@@ -770,12 +897,12 @@ fn main() {
             }
             Event::DeviceEvent { event, .. } => {
                 if let DeviceEvent::MouseMotion { delta } = event {
-                    let (dx, dy) = delta;
-                    camera_x += (dx as f32) * Player::MOUSE_SPEED;
-                    camera_y += (dy as f32) * Player::MOUSE_SPEED;
-
-                    camera_y = min_by(camera_y, 89.0, |a, b| a.partial_cmp(b).unwrap());
-                    camera_y = max_by(camera_y, -89.0, |a, b| a.partial_cmp(b).unwrap());
+                    // a replay brings its own, and the mouse is free in the pause menu
+                    if replay.is_none() && !paused {
+                        let (dx, dy) = delta;
+                        pending_mouse.0 += dx as f32;
+                        pending_mouse.1 += dy as f32;
+                    }
                 }
             }
             _ => *control_flow = ControlFlow::Poll,
@@ -0,0 +1,148 @@
+use crate::input::Action;
+use crate::settings::Settings;
+
+/// a value in the settings menu
+#[derive(Copy, Clone)]
+enum Entry {
+    SensitivityX,
+    SensitivityY,
+    InvertY,
+    Smoothing,
+    Acceleration,
+    Fov,
+    HeadBob,
+    MasterVolume,
+    AmbientVolume,
+    SfxVolume,
+    VoiceVolume,
+    Mute,
+}
+
+const ENTRIES: [Entry; 12] = [
+    Entry::SensitivityX,
+    Entry::SensitivityY,
+    Entry::InvertY,
+    Entry::Smoothing,
+    Entry::Acceleration,
+    Entry::Fov,
+    Entry::HeadBob,
+    Entry::MasterVolume,
+    Entry::AmbientVolume,
+    Entry::SfxVolume,
+    Entry::VoiceVolume,
+    Entry::Mute,
+];
+
+impl Entry {
+    fn label(self) -> &'static str {
+        match self {
+            Entry::SensitivityX => "Mouse sensitivity X",
+            Entry::SensitivityY => "Mouse sensitivity Y",
+            Entry::InvertY => "Invert Y",
+            Entry::Smoothing => "Mouse smoothing",
+            Entry::Acceleration => "Mouse acceleration",
+            Entry::Fov => "Field of view",
+            Entry::HeadBob => "Head bob",
+            Entry::MasterVolume => "Volume",
+            Entry::AmbientVolume => "Ambient volume",
+            Entry::SfxVolume => "Effects volume",
+            Entry::VoiceVolume => "Voice volume",
+            Entry::Mute => "Mute",
+        }
+    }
+
+    fn value(self, settings: &Settings) -> String {
+        let on_off = |on| if on { "on" } else { "off" }.to_owned();
+        let percent = |volume: f32| format!("{:.0}%", volume * 100.0);
+
+        match self {
+            Entry::SensitivityX => format!("{:.2}", settings.look.sensitivity_x),
+            Entry::SensitivityY => format!("{:.2}", settings.look.sensitivity_y),
+            Entry::InvertY => on_off(settings.look.invert_y),
+            Entry::Smoothing => format!("{:.1}", settings.look.smoothing),
+            Entry::Acceleration => format!("{:.1}", settings.look.acceleration),
+            Entry::Fov => format!("{:.0}", settings.look.fov),
+            Entry::HeadBob => on_off(settings.head_bob.enabled),
+            Entry::MasterVolume => percent(settings.audio.master.volume),
+            Entry::AmbientVolume => percent(settings.audio.ambient.volume),
+            Entry::SfxVolume => percent(settings.audio.sfx.volume),
+            Entry::VoiceVolume => percent(settings.audio.voice.volume),
+            Entry::Mute => on_off(settings.audio.master.muted),
+        }
+    }
+
+    /// `steps` is -1 or 1
+    fn change(self, settings: &mut Settings, steps: f32) {
+        let look = &mut settings.look;
+        let audio = &mut settings.audio;
+        let volume = |volume: &mut f32| *volume = (*volume + steps * 0.1).max(0.0).min(1.0);
+
+        match self {
+            Entry::SensitivityX => {
+                look.sensitivity_x = (look.sensitivity_x + steps * 0.01).max(0.01).min(1.0)
+            }
+            Entry::SensitivityY => {
+                look.sensitivity_y = (look.sensitivity_y + steps * 0.01).max(0.01).min(1.0)
+            }
+            Entry::InvertY => look.invert_y = !look.invert_y,
+            Entry::Smoothing => look.smoothing = (look.smoothing + steps * 0.1).max(0.0).min(0.9),
+            Entry::Acceleration => {
+                look.acceleration = (look.acceleration + steps * 0.1).max(0.0).min(2.0)
+            }
+            Entry::Fov => look.fov = (look.fov + steps * 5.0).max(50.0).min(110.0),
+            Entry::HeadBob => settings.head_bob.enabled = !settings.head_bob.enabled,
+            Entry::MasterVolume => volume(&mut audio.master.volume),
+            Entry::AmbientVolume => volume(&mut audio.ambient.volume),
+            Entry::SfxVolume => volume(&mut audio.sfx.volume),
+            Entry::VoiceVolume => volume(&mut audio.voice.volume),
+            Entry::Mute => audio.master.muted = !audio.master.muted,
+        }
+    }
+}
+
+/// the in-game settings menu. It's plain text, navigated with the menu actions, and saves the
+/// settings when it's closed.
+#[derive(Default)]
+pub struct SettingsMenu {
+    open: bool,
+    selected: usize,
+}
+
+impl SettingsMenu {
+    /// handles a menu action. Returns false for every other action, so it can go to the game.
+    pub fn handle(&mut self, action: Action, settings: &mut Settings) -> bool {
+        match action {
+            Action::Menu => {
+                if self.open {
+                    settings.save();
+                }
+                self.open = !self.open;
+            }
+            Action::MenuUp if self.open => {
+                self.selected = (self.selected + ENTRIES.len() - 1) % ENTRIES.len()
+            }
+            Action::MenuDown if self.open => self.selected = (self.selected + 1) % ENTRIES.len(),
+            Action::MenuLess if self.open => ENTRIES[self.selected].change(settings, -1.0),
+            Action::MenuMore if self.open => ENTRIES[self.selected].change(settings, 1.0),
+            _ => return false,
+        }
+        true
+    }
+
+    /// what the menu shows right now, empty while it's closed
+    pub fn text(&self, settings: &Settings) -> String {
+        if !self.open {
+            return String::new();
+        }
+
+        let mut text = format!(
+            "SETTINGS ({} to close)\n\n",
+            settings.key_bindings.name(Action::Menu)
+        );
+        for (index, entry) in ENTRIES.iter().enumerate() {
+            let marker = if index == self.selected { ">" } else { " " };
+            text += &format!("{} {}: {}\n", marker, entry.label(), entry.value(settings));
+        }
+        text
+    }
+}
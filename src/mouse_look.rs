@@ -0,0 +1,122 @@
+use crate::settings::LookSettings;
+
+/// turns mouse movement into camera rotation according to the look settings.
+#[derive(Default)]
+pub struct MouseLook {
+    /// rotation of the last tick, in degrees, for smoothing
+    last: (f32, f32),
+}
+
+impl MouseLook {
+    /// the camera can't look further up or down than this, in degrees
+    pub const MAX_PITCH: f32 = 89.0;
+
+    /// `delta` is all mouse movement since the last tick. Returns how far the camera turns this
+    /// tick as (yaw, pitch) in degrees, pitch positive downwards.
+    pub fn update(&mut self, settings: &LookSettings, delta: (f32, f32), dt: f32) -> (f32, f32) {
+        let speed = delta.0.hypot(delta.1) / dt;
+        let acceleration = 1.0 + settings.acceleration * speed / 1000.0;
+
+        let invert = if settings.invert_y { -1.0 } else { 1.0 };
+        let target = (
+            delta.0 * settings.sensitivity_x * acceleration,
+            delta.1 * settings.sensitivity_y * acceleration * invert,
+        );
+
+        let smoothing = settings.smoothing.max(0.0).min(0.9);
+        self.last = (
+            self.last.0 * smoothing + target.0 * (1.0 - smoothing),
+            self.last.1 * smoothing + target.1 * (1.0 - smoothing),
+        );
+        self.last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// no smoothing or acceleration, one degree per unit
+    fn raw() -> LookSettings {
+        LookSettings {
+            sensitivity_x: 1.0,
+            sensitivity_y: 1.0,
+            invert_y: false,
+            smoothing: 0.0,
+            acceleration: 0.0,
+            ..LookSettings::default()
+        }
+    }
+
+    #[test]
+    fn invert_y_flips_only_the_pitch() {
+        let settings = LookSettings {
+            invert_y: true,
+            ..raw()
+        };
+        assert_eq!(
+            MouseLook::default().update(&raw(), (3.0, 2.0), DT),
+            (3.0, 2.0)
+        );
+        assert_eq!(
+            MouseLook::default().update(&settings, (3.0, 2.0), DT),
+            (3.0, -2.0)
+        );
+    }
+
+    #[test]
+    fn each_axis_has_its_own_sensitivity() {
+        let settings = LookSettings {
+            sensitivity_x: 0.5,
+            sensitivity_y: 2.0,
+            ..raw()
+        };
+        assert_eq!(
+            MouseLook::default().update(&settings, (4.0, 4.0), DT),
+            (2.0, 8.0)
+        );
+    }
+
+    #[test]
+    fn smoothing_converges_to_the_raw_delta() {
+        let settings = LookSettings {
+            smoothing: 0.5,
+            ..raw()
+        };
+        let mut look = MouseLook::default();
+
+        let first = look.update(&settings, (10.0, -4.0), DT);
+        assert_eq!(first, (5.0, -2.0));
+
+        let mut last = first;
+        for _ in 0..30 {
+            last = look.update(&settings, (10.0, -4.0), DT);
+        }
+        assert!((last.0 - 10.0).abs() < 0.001 && (last.1 + 4.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn smoothing_is_capped() {
+        let settings = LookSettings {
+            smoothing: 5.0,
+            ..raw()
+        };
+        let (yaw, _) = MouseLook::default().update(&settings, (10.0, 0.0), DT);
+        assert!((yaw - 1.0).abs() < 0.001, "yaw is {}", yaw);
+    }
+
+    #[test]
+    fn fast_movements_turn_farther() {
+        let settings = LookSettings {
+            acceleration: 1.0,
+            ..raw()
+        };
+        // 1 unit per tick is 60 per second, 10 units per tick 600
+        let (slow, _) = MouseLook::default().update(&settings, (1.0, 0.0), DT);
+        let (fast, _) = MouseLook::default().update(&settings, (10.0, 0.0), DT);
+        assert!((slow - 1.06).abs() < 0.001, "slow is {}", slow);
+        assert!((fast - 16.0).abs() < 0.001, "fast is {}", fast);
+    }
+}
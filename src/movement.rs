@@ -0,0 +1,50 @@
+use crate::collision::{move_and_slide, CollisionGrid};
+use crate::input::InputController;
+use crate::player::Player;
+
+/// the directions to the left and to the front of a camera turned by `yaw` degrees, on the
+/// ground as (x, z). Pitch and the roll of leaning don't change where the player walks.
+pub fn ground_directions(yaw: f32) -> ((f32, f32), (f32, f32)) {
+    let (sin, cos) = yaw.to_radians().sin_cos();
+    ((cos, sin), (-sin, cos))
+}
+
+/// where the player ends up after walking for `dt` seconds from `position`, looking `yaw`
+/// degrees to the side. Walls are slid along, the physics is only told to get there and adds
+/// jumping and falling on top. Nothing here needs the engine, so replays can be checked
+/// without it.
+pub fn walk(
+    grid: &impl CollisionGrid,
+    controls: &InputController,
+    yaw: f32,
+    speed: f32,
+    position: (f32, f32),
+    dt: f32,
+) -> (f32, f32) {
+    let (side, front) = ground_directions(yaw);
+
+    let mut offset = (0.0, 0.0);
+    let mut add = |(x, z): (f32, f32), sign: f32| {
+        offset.0 += x * sign;
+        offset.1 += z * sign;
+    };
+    if controls.move_right {
+        add(side, -1.0);
+    }
+    if controls.move_left {
+        add(side, 1.0);
+    }
+    if controls.move_forward {
+        add(front, 1.0);
+    }
+    if controls.move_backward {
+        add(front, -1.0);
+    }
+
+    move_and_slide(
+        grid,
+        position,
+        (offset.0 * speed * dt, offset.1 * speed * dt),
+        Player::RADIUS,
+    )
+}
@@ -0,0 +1,256 @@
+use std::iter;
+
+use rg3d::physics::na::Vector3;
+use serde::Deserialize;
+
+use crate::audio::AudioManager;
+use crate::collision::CollisionGrid;
+use crate::visibility::{can_see, squeezes_through_corner, tiles_between};
+
+/// tuning of how walls muffle sounds, part of `assets/level.ron`
+#[derive(Deserialize, Copy, Clone)]
+pub struct OcclusionOptions {
+    /// gain left after a sound went through one wall or closed door
+    pub wall_gain: f32,
+    /// sounds never get quieter than this, no matter how many walls are in between
+    pub min_gain: f32,
+    /// seconds between two updates, walking the grid for every sound each tick is too slow
+    pub interval: f32,
+    /// sources further away than this are inaudible anyway and keep their last value
+    pub max_distance: f32,
+    /// cutoff of the low-pass after a sound went through one wall or closed door, in Hz. Every
+    /// further wall lowers it by the same ratio again.
+    pub wall_cutoff: f32,
+    /// the low-pass never goes lower than this, in Hz
+    pub min_cutoff: f32,
+}
+
+/// cutoff of the low-pass with nothing in the way, in Hz. Nothing anyone can hear is filtered.
+pub const OPEN_CUTOFF: f32 = 22_050.0;
+
+/// what the walls between a sound and the listener do to it
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Occlusion {
+    /// how much of the sound gets through, from 1 down to `OcclusionOptions::min_gain`
+    pub gain: f32,
+    /// cutoff of the low-pass that muffles it, in Hz. `OPEN_CUTOFF` leaves it as it is.
+    pub cutoff: f32,
+}
+
+/// sideways offsets of the lines between source and listener. A sound is only occluded
+/// completely when all of them are blocked, if some get through it's just obstructed.
+const LINE_OFFSETS: [f32; 3] = [-0.35, 0.0, 0.35];
+
+/// quiets and muffles the spatial sounds depending on the walls and closed doors between them
+/// and the listener. Both are handed to the `AudioManager`, which fades them per source.
+#[derive(Default)]
+pub struct SoundOcclusion {
+    /// seconds until the next update
+    timer: f32,
+}
+
+impl SoundOcclusion {
+    pub fn update(
+        &mut self,
+        audio: &mut AudioManager,
+        grid: &impl CollisionGrid,
+        listener: Vector3<f32>,
+        options: &OcclusionOptions,
+        dt: f32,
+    ) {
+        self.timer -= dt;
+        if self.timer > 0.0 {
+            return;
+        }
+        self.timer = options.interval;
+
+        for (handle, position) in audio.spatial_sources() {
+            let distance = (position.x - listener.x).hypot(position.z - listener.z);
+            if distance > options.max_distance {
+                continue;
+            }
+
+            let occlusion = occlusion(
+                grid,
+                (listener.x, listener.z),
+                (position.x, position.z),
+                options,
+            );
+            audio.set_occlusion(handle, occlusion);
+        }
+    }
+}
+
+/// what happens to a sound on its way from `from` to `to`. Lines that get through unblocked keep
+/// the gain and the high frequencies up, so a sound that's only partly behind a wall is
+/// muffled less than one behind it completely.
+pub fn occlusion(
+    grid: &impl CollisionGrid,
+    from: (f32, f32),
+    to: (f32, f32),
+    options: &OcclusionOptions,
+) -> Occlusion {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.hypot(dy).max(f32::EPSILON);
+    let side = (-dy / length, dx / length);
+
+    let walls = LINE_OFFSETS
+        .iter()
+        .map(|&offset| {
+            let tile = |(x, y): (f32, f32)| {
+                (
+                    (x + side.0 * offset).round() as i32,
+                    (y + side.1 * offset).round() as i32,
+                )
+            };
+            count_walls(grid, tile(from), tile(to))
+        })
+        .collect::<Vec<_>>();
+    let lines = walls.len() as f32;
+
+    let gain = walls
+        .iter()
+        .map(|&walls| options.wall_gain.powi(walls as i32))
+        .sum::<f32>()
+        / lines;
+    let average_walls = walls.iter().sum::<usize>() as f32 / lines;
+    let cutoff = OPEN_CUTOFF * (options.wall_cutoff / OPEN_CUTOFF).powf(average_walls);
+
+    Occlusion {
+        gain: gain.max(options.min_gain),
+        cutoff: cutoff.max(options.min_cutoff),
+    }
+}
+
+/// walls on the line between both tiles, not counting the tiles themselves since sources are
+/// often placed right at a wall. Solid tiles next to each other are one wall, no matter how
+/// thick it is, and so are two walls touching at their corners.
+fn count_walls(grid: &impl CollisionGrid, from: (i32, i32), to: (i32, i32)) -> usize {
+    if can_see(grid, from, to) {
+        return 0;
+    }
+
+    let line = iter::once(from)
+        .chain(tiles_between(from, to))
+        .chain(iter::once(to))
+        .collect::<Vec<_>>();
+
+    let mut walls = 0;
+    let mut in_wall = false;
+    for step in line.windows(2) {
+        let blocked = (step[1] != to && grid.is_solid(step[1]))
+            || squeezes_through_corner(grid, step[0], step[1]);
+        if blocked && !in_wall {
+            walls += 1;
+        }
+        in_wall = blocked;
+    }
+    walls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::tests::{two_rooms, ClosedDoor};
+    use crate::level_generator::Level;
+
+    fn options() -> OcclusionOptions {
+        OcclusionOptions {
+            wall_gain: 0.3,
+            min_gain: 0.0,
+            interval: 0.2,
+            max_distance: 20.0,
+            wall_cutoff: 2000.0,
+            min_cutoff: 500.0,
+        }
+    }
+
+    #[test]
+    fn open_door_lets_the_sound_through() {
+        let level = two_rooms();
+
+        assert_eq!(count_walls(&level, (3, 1), (3, 5)), 0);
+        let occlusion = occlusion(&level, (3.0, 1.0), (3.0, 5.0), &options());
+        assert!((occlusion.gain - 1.0).abs() < 0.001, "{:?}", occlusion);
+        assert_eq!(occlusion.cutoff, OPEN_CUTOFF);
+    }
+
+    #[test]
+    fn closed_door_muffles_like_a_wall() {
+        let grid = ClosedDoor {
+            level: two_rooms(),
+            door: (3, 3),
+        };
+
+        assert_eq!(count_walls(&grid, (3, 1), (3, 5)), 1);
+        let occlusion = occlusion(&grid, (3.0, 1.0), (3.0, 5.0), &options());
+        assert!((occlusion.gain - 0.3).abs() < 0.001, "{:?}", occlusion);
+        assert!((occlusion.cutoff - 2000.0).abs() < 1.0, "{:?}", occlusion);
+    }
+
+    #[test]
+    fn thick_walls_count_once() {
+        let level = Level::parse(
+            "#####
+             #...#
+             #####
+             #####
+             #...#
+             #####",
+        );
+
+        assert_eq!(count_walls(&level, (2, 1), (2, 4)), 1);
+    }
+
+    #[test]
+    fn no_sound_through_diagonal_gaps() {
+        let level = Level::parse(
+            "####
+             #.##
+             ##.#
+             ####",
+        );
+
+        assert_eq!(count_walls(&level, (1, 1), (2, 2)), 1);
+        assert_eq!(count_walls(&level, (2, 2), (1, 1)), 1);
+    }
+
+    #[test]
+    fn more_walls_muffle_more() {
+        let level = Level::parse(
+            "#######
+             #.#.#.#
+             #######",
+        );
+        let options = OcclusionOptions {
+            min_gain: 0.05,
+            ..options()
+        };
+
+        let one = occlusion(&level, (1.0, 1.0), (3.0, 1.0), &options);
+        let two = occlusion(&level, (1.0, 1.0), (5.0, 1.0), &options);
+        assert!(two.gain < one.gain && two.cutoff < one.cutoff);
+        assert_eq!(two.cutoff, options.min_cutoff);
+        assert!((two.gain - 0.09).abs() < 0.001, "{:?}", two);
+    }
+
+    #[test]
+    fn sounds_partly_behind_a_wall_are_muffled_less() {
+        let level = Level::parse(
+            "#####
+             #...#
+             ##..#
+             #...#
+             #####",
+        );
+
+        // only the line on the right gets past the wall
+        let partly = occlusion(&level, (1.4, 1.0), (1.4, 3.0), &options());
+        assert!(partly.gain > 0.3 && partly.gain < 1.0, "{:?}", partly);
+        assert!(
+            partly.cutoff > 2000.0 && partly.cutoff < OPEN_CUTOFF,
+            "{:?}",
+            partly
+        );
+    }
+}
@@ -0,0 +1,17 @@
+use serde::Deserialize;
+
+/// per level settings for the oxygen supply of the player, see `assets/level.ron`
+#[derive(Deserialize, Clone)]
+pub struct OxygenOptions {
+    /// oxygen used per second while walking or standing, a full meter is 1
+    pub depletion_rate: f32,
+    /// `depletion_rate` is multiplied by this while running
+    pub run_multiplier: f32,
+    /// oxygen added by using a tank from the inventory
+    pub tank_refill: f32,
+    /// how many tanks that can be picked up are placed in a chunk, inclusive. Themes with
+    /// oxygen tank props add theirs on top.
+    pub tanks: (usize, usize),
+    /// name of the prop used for the tanks, see `assets/props.ron`
+    pub tank_prop: String,
+}
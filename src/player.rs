@@ -1,61 +1,458 @@
-use std::time::Instant;
+use std::f32::consts::PI;
 
-#[derive(PartialEq)]
-pub enum WalkState {
+use crate::inventory::{Inventory, Item};
+use crate::oxygen::OxygenOptions;
+use crate::settings::FootstepSettings;
+
+/// what the player is doing right now. Every state has its own speed, camera height and
+/// loudness, `Player::update` decides which one applies.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PlayerState {
     Standing,
     Walking,
     Running,
+    /// crouched, moving or not
+    Crouching,
+    /// ran out of stamina, can't run until enough of it came back
+    Exhausted,
+    /// not on the ground, e.g. while jumping
+    Falling,
+    /// out of health, nothing works anymore
+    Dead,
+}
+
+impl PlayerState {
+    /// horizontal speed in units per second
+    pub fn speed(self) -> f32 {
+        match self {
+            PlayerState::Standing | PlayerState::Walking | PlayerState::Falling => Player::SPEED,
+            PlayerState::Running => Player::SPEED + Player::EXTRA_RUN_SPEED,
+            PlayerState::Crouching => Player::SPEED * 0.5,
+            PlayerState::Exhausted => Player::SPEED * 0.6,
+            PlayerState::Dead => 0.0,
+        }
+    }
+
+    /// added to the eye height of the character controller. Crouching is missing here, the
+    /// controller already shrinks the capsule for it.
+    pub fn eye_offset(self) -> f32 {
+        match self {
+            // hunched over, catching breath
+            PlayerState::Exhausted => -0.04,
+            // lying on the floor
+            PlayerState::Dead => -0.35,
+            _ => 0.0,
+        }
+    }
+
+    pub fn footstep_gain(self) -> f32 {
+        match self {
+            PlayerState::Walking => 0.07,
+            PlayerState::Running => 0.15,
+            PlayerState::Crouching => 0.03,
+            PlayerState::Exhausted => 0.06,
+            PlayerState::Standing | PlayerState::Falling | PlayerState::Dead => 0.0,
+        }
+    }
+
+    /// leaning needs both feet on the ground and some calm
+    pub fn can_lean(self) -> bool {
+        matches!(
+            self,
+            PlayerState::Standing | PlayerState::Walking | PlayerState::Crouching
+        )
+    }
+
+    /// how much noise the player makes, from 0 (silent) to 1 (sprinting)
+    pub fn noise_level(self) -> f32 {
+        match self {
+            PlayerState::Standing | PlayerState::Falling | PlayerState::Dead => 0.0,
+            PlayerState::Crouching => 0.1,
+            PlayerState::Walking => 0.4,
+            // heavy breathing
+            PlayerState::Exhausted => 0.5,
+            PlayerState::Running => 1.0,
+        }
+    }
+}
+
+/// what the player wants to do this tick
+#[derive(Default, Copy, Clone)]
+pub struct MovementIntent {
+    pub moving: bool,
+    pub run: bool,
+    pub crouch: bool,
 }
 
 pub struct Player {
-    pub walk_state: WalkState,
-    clock: Option<Instant>,
+    state: PlayerState,
+    /// position within the current pair of steps, from 0 to 2π. A foot hits the ground at 0
+    /// and at π.
+    stride_phase: f32,
+    /// game time of the last step
+    last_step: f32,
+    /// from 0 to 1, running drains it
+    stamina: f32,
+    /// set when running emptied the stamina, cleared when enough of it came back
+    exhausted: bool,
+    /// from 0 to 1, the player suffocates when it's used up
+    oxygen: f32,
+    /// from 0 to 1, the player dies when it's used up
+    health: f32,
+    /// seconds until the next gasp for air takes health, while the oxygen is used up
+    suffocation_timer: f32,
+    /// how hard the last hit was, fades out. From 0 to 1, drives the hit feedback.
+    hurt: f32,
+    /// set by `damage`, cleared by `take_hit`
+    hit: bool,
+    /// what killed the player
+    cause_of_death: Option<&'static str>,
+    pub inventory: Inventory,
 }
 
 impl Player {
-    pub const SPEED: f32 = 0.0175;
-    pub const EXTRA_RUN_SPEED: f32 = 0.02;
-    pub const MOUSE_SPEED: f32 = 0.15;
+    /// walking speed in units per second
+    pub const SPEED: f32 = 1.05;
+    /// added to `SPEED` while running, units per second
+    pub const EXTRA_RUN_SPEED: f32 = 1.2;
+    /// the player collides with walls as a circle of this radius
+    pub const RADIUS: f32 = 0.2;
+
+    /// steps closer together than this are swallowed, so jittering against a wall doesn't
+    /// sound like tap dancing. In seconds.
+    const MIN_STEP_INTERVAL: f32 = 0.2;
+
+    /// stamina per second
+    const STAMINA_DRAIN: f32 = 0.15;
+    const STAMINA_REGEN_WALKING: f32 = 0.08;
+    const STAMINA_REGEN_STANDING: f32 = 0.16;
+    /// stamina needed to recover from being exhausted
+    const RECOVERED_STAMINA: f32 = 0.4;
 
-    pub fn run(&mut self) {
-        if self.walk_state == WalkState::Walking {
-            self.walk_state = WalkState::Running;
+    /// health lost per gasp without oxygen, a gasp happens every `SUFFOCATION_INTERVAL` seconds
+    const SUFFOCATION_DAMAGE: f32 = 0.2;
+    const SUFFOCATION_INTERVAL: f32 = 1.0;
+    /// `hurt` lost per second
+    const HURT_FADE: f32 = 1.5;
+
+    pub fn state(&self) -> PlayerState {
+        self.state
+    }
+
+    /// moves to the next state and updates stamina and oxygen. Must be called once per tick
+    /// before the player is moved.
+    pub fn update(
+        &mut self,
+        intent: MovementIntent,
+        on_ground: bool,
+        dt: f32,
+        oxygen: &OxygenOptions,
+    ) {
+        self.hurt = (self.hurt - Self::HURT_FADE * dt).max(0.0);
+
+        if self.state == PlayerState::Dead {
+            return;
         }
+
+        self.update_suffocation(dt);
+
+        self.state = if self.health == 0.0 {
+            PlayerState::Dead
+        } else if !on_ground {
+            PlayerState::Falling
+        } else if intent.crouch {
+            PlayerState::Crouching
+        } else if self.exhausted {
+            PlayerState::Exhausted
+        } else if intent.moving && intent.run {
+            PlayerState::Running
+        } else if intent.moving {
+            PlayerState::Walking
+        } else {
+            PlayerState::Standing
+        };
+
+        self.update_stamina(intent.moving, dt);
+        self.update_oxygen(dt, oxygen);
     }
 
-    pub fn walk(&mut self) {
-        self.walk_state = WalkState::Walking;
-        if self.clock.is_none() {
-            self.clock = Some(Instant::now());
+    /// advances the stride by the distance the player actually moved. Returns whether a foot
+    /// hit the ground, i.e. a step sound should be played. `now` is the time of the
+    /// `GameClock`.
+    pub fn advance_stride(&mut self, distance: f32, now: f32, settings: &FootstepSettings) -> bool {
+        let stride = match self.state {
+            PlayerState::Standing | PlayerState::Falling | PlayerState::Dead => return false,
+            PlayerState::Running => settings.run_stride,
+            PlayerState::Walking | PlayerState::Crouching | PlayerState::Exhausted => {
+                settings.walk_stride
+            }
+        };
+
+        let old_phase = self.stride_phase;
+        self.stride_phase = (old_phase + distance / stride * PI) % (2.0 * PI);
+
+        // wrapping around from 2π to 0 counts as well
+        let stepped = (old_phase / PI).floor() != (self.stride_phase / PI).floor();
+
+        if stepped && now - self.last_step >= Self::MIN_STEP_INTERVAL {
+            self.last_step = now;
+            return true;
         }
+
+        false
     }
 
-    pub fn should_play_step_sound(&mut self) -> bool {
-        if let Some(clock) = self.clock {
-            let time_between_steps = if self.walk_state == WalkState::Running {
-                350u128
-            } else {
-                550u128
-            };
-            if clock.elapsed().as_millis() > time_between_steps {
-                self.clock = Some(Instant::now());
-                return true;
+    pub fn stride_phase(&self) -> f32 {
+        self.stride_phase
+    }
+
+    /// drains the stamina while running and regenerates it otherwise, faster when standing
+    /// still.
+    fn update_stamina(&mut self, moving: bool, dt: f32) {
+        let change = match self.state {
+            PlayerState::Running => -Self::STAMINA_DRAIN,
+            _ if moving => Self::STAMINA_REGEN_WALKING,
+            _ => Self::STAMINA_REGEN_STANDING,
+        };
+        self.stamina = (self.stamina + change * dt).max(0.0).min(1.0);
+
+        if self.stamina == 0.0 {
+            self.exhausted = true;
+        } else if self.stamina >= Self::RECOVERED_STAMINA {
+            self.exhausted = false;
+        }
+    }
+
+    /// from 0 to 1, for the HUD
+    pub fn stamina(&self) -> f32 {
+        self.stamina
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
+    }
+
+    /// uses up oxygen, faster while running.
+    fn update_oxygen(&mut self, dt: f32, options: &OxygenOptions) {
+        let rate = if self.state == PlayerState::Running {
+            options.depletion_rate * options.run_multiplier
+        } else {
+            options.depletion_rate
+        };
+        self.oxygen = (self.oxygen - rate * dt).max(0.0);
+    }
+
+    pub fn refill_oxygen(&mut self, amount: f32) {
+        self.oxygen = (self.oxygen + amount).min(1.0);
+    }
+
+    /// from 0 to 1, for the HUD
+    pub fn oxygen(&self) -> f32 {
+        self.oxygen
+    }
+
+    /// takes the item out of the inventory and uses it. Returns false if the player doesn't have
+    /// it or it can't be used on its own, like keycards or batteries.
+    pub fn use_item(&mut self, item: Item, oxygen: &OxygenOptions) -> bool {
+        match item {
+            Item::OxygenTank if self.inventory.remove(item) => {
+                self.refill_oxygen(oxygen.tank_refill);
+                true
             }
+            _ => false,
         }
-        return false;
     }
 
-    pub fn stand(&mut self) {
-        self.walk_state = WalkState::Standing;
-        self.clock = None;
+    /// takes one gasp worth of health every now and then while the oxygen is used up.
+    fn update_suffocation(&mut self, dt: f32) {
+        if self.oxygen > 0.0 {
+            self.suffocation_timer = Self::SUFFOCATION_INTERVAL;
+            return;
+        }
+
+        self.suffocation_timer -= dt;
+        if self.suffocation_timer <= 0.0 {
+            self.suffocation_timer += Self::SUFFOCATION_INTERVAL;
+            self.damage(Self::SUFFOCATION_DAMAGE, "suffocated");
+        }
+    }
+
+    /// hurts the player. `cause` finishes the sentence "You ..." on the game over screen, in
+    /// case this kills them. The player dies on the next `update`.
+    pub fn damage(&mut self, amount: f32, cause: &'static str) {
+        if self.state == PlayerState::Dead || amount <= 0.0 {
+            return;
+        }
+
+        self.health = (self.health - amount).max(0.0);
+        self.hurt = (self.hurt + 0.5 + amount * 2.0).min(1.0);
+        self.hit = true;
+        if self.health == 0.0 {
+            self.cause_of_death = Some(cause);
+        }
+    }
+
+    /// from 0 to 1, for the HUD
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    /// from 0 to 1, how strong the screen flash and camera shake of the last hit are right now
+    pub fn hurt(&self) -> f32 {
+        self.hurt
+    }
+
+    /// whether the player was hit since the last call, to play the hurt sound once per hit
+    pub fn take_hit(&mut self) -> bool {
+        std::mem::replace(&mut self.hit, false)
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.state == PlayerState::Dead
+    }
+
+    /// finishes the sentence "You ..." on the game over screen
+    pub fn cause_of_death(&self) -> Option<&'static str> {
+        self.cause_of_death
     }
 }
 
 impl Default for Player {
     fn default() -> Self {
         Self {
-            walk_state: WalkState::Standing,
-            clock: None,
+            state: PlayerState::Standing,
+            stride_phase: 0.0,
+            last_step: 0.0,
+            stamina: 1.0,
+            exhausted: false,
+            oxygen: 1.0,
+            health: 1.0,
+            suffocation_timer: Self::SUFFOCATION_INTERVAL,
+            hurt: 0.0,
+            hit: false,
+            cause_of_death: None,
+            inventory: Inventory::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::GameClock;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn oxygen() -> OxygenOptions {
+        OxygenOptions {
+            depletion_rate: 0.0,
+            run_multiplier: 1.0,
+            tank_refill: 0.35,
+            tanks: (0, 0),
+            tank_prop: String::new(),
+        }
+    }
+
+    fn intent(moving: bool, run: bool) -> MovementIntent {
+        MovementIntent {
+            moving,
+            run,
+            crouch: false,
+        }
+    }
+
+    /// ticks the player and the clock for `seconds`
+    fn run_for(player: &mut Player, clock: &mut GameClock, intent: MovementIntent, seconds: f32) {
+        for _ in 0..(seconds / DT).round() as usize {
+            player.update(intent, true, DT, &oxygen());
+            clock.advance(DT);
         }
     }
+
+    #[test]
+    fn no_step_within_min_step_interval() {
+        let settings = FootstepSettings::default();
+        let half_stride = settings.walk_stride;
+        let mut player = Player::default();
+        let mut clock = GameClock::default();
+
+        run_for(&mut player, &mut clock, intent(true, false), 0.5);
+        assert_eq!(player.state(), PlayerState::Walking);
+        assert!(player.advance_stride(half_stride, clock.now(), &settings));
+
+        // the next foot comes down right away, e.g. while jittering against a wall
+        clock.advance(Player::MIN_STEP_INTERVAL / 2.0);
+        assert!(!player.advance_stride(half_stride, clock.now(), &settings));
+
+        clock.advance(Player::MIN_STEP_INTERVAL);
+        assert!(player.advance_stride(half_stride, clock.now(), &settings));
+    }
+
+    #[test]
+    fn one_step_per_stride() {
+        let settings = FootstepSettings::default();
+        let mut player = Player::default();
+        let mut clock = GameClock::default();
+        run_for(&mut player, &mut clock, intent(true, false), 0.5);
+
+        let mut steps = 0;
+        for _ in 0..600 {
+            clock.advance(DT);
+            if player.advance_stride(Player::SPEED * DT, clock.now(), &settings) {
+                steps += 1;
+            }
+        }
+
+        let expected = Player::SPEED * 600.0 * DT / settings.walk_stride;
+        assert!((steps as f32 - expected).abs() <= 1.0, "{} steps", steps);
+    }
+
+    #[test]
+    fn no_steps_while_standing() {
+        let settings = FootstepSettings::default();
+        let mut player = Player::default();
+        let mut clock = GameClock::default();
+        run_for(&mut player, &mut clock, intent(false, false), 1.0);
+
+        assert!(!player.advance_stride(settings.walk_stride, clock.now(), &settings));
+    }
+
+    #[test]
+    fn running_exhausts_until_stamina_recovered() {
+        let mut player = Player::default();
+        let mut clock = GameClock::default();
+
+        // a full stamina lasts this long while running
+        let time_to_exhaust = 1.0 / Player::STAMINA_DRAIN;
+        let mut seconds = 0.0;
+        while !player.is_exhausted() {
+            run_for(&mut player, &mut clock, intent(true, true), DT);
+            seconds += DT;
+            if !player.is_exhausted() {
+                assert_eq!(player.state(), PlayerState::Running);
+            }
+            assert!(seconds < time_to_exhaust + 0.1, "never got exhausted");
+        }
+        assert!(seconds > time_to_exhaust - 0.1, "too early: {}", seconds);
+        assert_eq!(player.stamina(), 0.0);
+
+        // can't run while exhausted, even when trying to
+        run_for(&mut player, &mut clock, intent(true, true), DT);
+        assert_eq!(player.state(), PlayerState::Exhausted);
+
+        // standing still brings the stamina back, running is possible again once there is
+        // enough of it
+        let time_to_recover = Player::RECOVERED_STAMINA / Player::STAMINA_REGEN_STANDING;
+        let standing = intent(false, false);
+        run_for(&mut player, &mut clock, standing, time_to_recover - 0.2);
+        assert!(player.is_exhausted());
+        assert!(player.stamina() < Player::RECOVERED_STAMINA);
+
+        run_for(&mut player, &mut clock, standing, 0.4);
+        assert!(!player.is_exhausted());
+        assert!(player.stamina() >= Player::RECOVERED_STAMINA);
+
+        run_for(&mut player, &mut clock, intent(true, true), DT);
+        assert_eq!(player.state(), PlayerState::Running);
+    }
 }
@@ -0,0 +1,246 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::level_generator::{Level, WallInfo};
+use crate::ron_file::load_ron;
+
+/// a prop and the rules for placing it. Props are defined in `assets/props.ron` and referenced
+/// by name from the themes.
+#[derive(Deserialize)]
+pub struct PropDefinition {
+    pub name: String,
+    pub model: String,
+    #[serde(default)]
+    pub sound: Option<PropSound>,
+    pub placement: Placement,
+    pub rotation: RotationRule,
+    /// how many of these props are placed in a room, inclusive
+    #[serde(default = "default_count")]
+    pub count: (usize, usize),
+    /// minimum distance in tiles to every other prop in the same room
+    #[serde(default)]
+    pub min_spacing: f32,
+    /// how often a position is rolled before the prop is skipped
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: usize,
+    /// the prop is an oxygen tank the player can pick up, not just decoration
+    #[serde(default)]
+    pub oxygen_tank: bool,
+}
+
+/// a looping sound played at the position of the prop
+#[derive(Deserialize)]
+pub struct PropSound {
+    pub path: String,
+    pub gain: f32,
+}
+
+#[derive(Deserialize, Copy, Clone, PartialEq)]
+pub enum Placement {
+    /// on a tile with a full wall on at least one side
+    AgainstWall,
+    /// on a tile with walls on two adjacent sides
+    InCorner,
+    /// as close to the middle of the room as possible
+    RoomCenter,
+    /// on any tile of the room
+    Anywhere,
+}
+
+#[derive(Deserialize, Copy, Clone)]
+pub enum RotationRule {
+    /// always the given angle in degrees
+    Fixed(f32),
+    /// one of 0, 90, 180 or 270 degrees
+    Random90,
+    /// any angle
+    Random,
+    /// turned so the back of the prop is against the wall. Falls back to `Random90` for
+    /// props that aren't next to a wall.
+    FaceAwayFromWall,
+}
+
+fn default_count() -> (usize, usize) {
+    (1, 1)
+}
+
+fn default_max_attempts() -> usize {
+    16
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Side {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Side {
+    /// rotation (in degrees) of a prop with its back against this wall
+    pub fn rotation(self) -> f32 {
+        match self {
+            Side::Up => 0.0,
+            Side::Right => 270.0,
+            Side::Down => 180.0,
+            Side::Left => 90.0,
+        }
+    }
+
+    /// offset from the tile center to the wall on this side
+    pub fn offset(self) -> (f32, f32) {
+        match self {
+            Side::Up => (0.0, -0.5),
+            Side::Right => (0.5, 0.0),
+            Side::Down => (0.0, 0.5),
+            Side::Left => (-0.5, 0.0),
+        }
+    }
+
+    /// all sides of a tile with a complete wall, in the order up, right, down, left
+    pub fn walls(walls: &WallInfo) -> Vec<Side> {
+        let mut sides = Vec::new();
+        if walls.up_left && walls.up_right {
+            sides.push(Side::Up);
+        }
+        if walls.right_up && walls.right_down {
+            sides.push(Side::Right);
+        }
+        if walls.down_left && walls.down_right {
+            sides.push(Side::Down);
+        }
+        if walls.left_up && walls.left_down {
+            sides.push(Side::Left);
+        }
+        sides
+    }
+}
+
+pub struct PropPlacement {
+    pub tile: (usize, usize),
+    /// rotation around the y axis in degrees
+    pub rotation: f32,
+    /// the wall the prop is standing against, if any
+    pub wall: Option<Side>,
+}
+
+pub fn load_props(path: &str) -> Vec<PropDefinition> {
+    load_ron(path)
+}
+
+/// finds positions for a prop in the room. `placed` contains the tiles of all props that are
+/// already in the room and gets the new ones appended. Gives up after `max_attempts` rolls per
+/// prop, so a room without a suitable tile simply doesn't get the prop.
+pub fn place_props(
+    level: &Level,
+    room: &[(usize, usize)],
+    prop: &PropDefinition,
+    placed: &mut Vec<(usize, usize)>,
+    rng: &mut StdRng,
+) -> Vec<PropPlacement> {
+    let candidates = candidates(level, room, prop.placement);
+
+    if candidates.is_empty() {
+        return Vec::new();
+    }
+
+    let count = rng.gen_range(prop.count.0, prop.count.1 + 1);
+
+    let mut placements = Vec::new();
+
+    for _ in 0..count {
+        for attempt in 0..prop.max_attempts {
+            // the room center is deterministic, the candidates are sorted by distance to it
+            let &(tile, wall) = if prop.placement == Placement::RoomCenter {
+                match candidates.get(attempt) {
+                    Some(candidate) => candidate,
+                    None => break,
+                }
+            } else {
+                candidates.choose(rng).unwrap()
+            };
+
+            let too_close = placed
+                .iter()
+                .any(|&other| other == tile || distance(other, tile) < prop.min_spacing);
+            if too_close {
+                continue;
+            }
+
+            let rotation = match (prop.rotation, wall) {
+                (RotationRule::Fixed(angle), _) => angle,
+                (RotationRule::Random, _) => rng.gen_range(0.0, 360.0),
+                (RotationRule::FaceAwayFromWall, Some(side)) => side.rotation(),
+                (RotationRule::Random90, _) | (RotationRule::FaceAwayFromWall, None) => {
+                    *[0.0f32, 90.0, 180.0, 270.0].choose(rng).unwrap()
+                }
+            };
+
+            placed.push(tile);
+            placements.push(PropPlacement {
+                tile,
+                rotation,
+                wall,
+            });
+
+            break;
+        }
+    }
+
+    placements
+}
+
+/// all tiles of the room that satisfy the placement rule, together with the wall they are next
+/// to.
+pub fn candidates(
+    level: &Level,
+    room: &[(usize, usize)],
+    placement: Placement,
+) -> Vec<((usize, usize), Option<Side>)> {
+    match placement {
+        Placement::AgainstWall => room
+            .iter()
+            .flat_map(|&(x, y)| {
+                Side::walls(&level.map[x][y].walls)
+                    .into_iter()
+                    .map(move |side| ((x, y), Some(side)))
+            })
+            .collect(),
+        Placement::InCorner => room
+            .iter()
+            .filter_map(|&(x, y)| {
+                let sides = Side::walls(&level.map[x][y].walls);
+                let is_corner = sides.iter().any(|&a| {
+                    sides.iter().any(|&b| {
+                        (a == Side::Up || a == Side::Down) && (b == Side::Left || b == Side::Right)
+                    })
+                });
+                if is_corner {
+                    Some(((x, y), sides.first().cloned()))
+                } else {
+                    None
+                }
+            })
+            .collect(),
+        Placement::RoomCenter => {
+            let count = room.len() as f32;
+            let center_x = room.iter().map(|&(x, _)| x as f32).sum::<f32>() / count;
+            let center_y = room.iter().map(|&(_, y)| y as f32).sum::<f32>() / count;
+
+            let mut tiles = room.iter().map(|&tile| (tile, None)).collect::<Vec<_>>();
+            tiles.sort_by(|&(a, _), &(b, _)| {
+                let distance_a = (a.0 as f32 - center_x).hypot(a.1 as f32 - center_y);
+                let distance_b = (b.0 as f32 - center_x).hypot(b.1 as f32 - center_y);
+                distance_a.partial_cmp(&distance_b).unwrap()
+            });
+            tiles
+        }
+        Placement::Anywhere => room.iter().map(|&tile| (tile, None)).collect(),
+    }
+}
+
+fn distance(a: (usize, usize), b: (usize, usize)) -> f32 {
+    (a.0 as f32 - b.0 as f32).hypot(a.1 as f32 - b.1 as f32)
+}
@@ -0,0 +1,283 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::input::Action;
+
+/// everything the player did with the controls during one tick of the fixed timestep
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct TickInput {
+    /// actions whose key or button went down (true) or up (false), in order
+    pub actions: Vec<(Action, bool)>,
+    /// how far the camera turned as (yaw, pitch), in degrees. It's recorded after the look
+    /// settings were applied, so replays don't depend on them.
+    pub look: (f32, f32),
+    /// the window lost focus and doesn't hear about keys being released anymore, so all of them
+    /// count as released
+    pub focus_lost: bool,
+}
+
+/// a played game, from the start of a level until the player quit or started over. Replaying it
+/// with the same seed does exactly the same thing again.
+///
+/// In the file the seed comes first, followed by one `TickInput` per line. That way it can be
+/// written while playing, and a crash only loses the last few ticks.
+pub struct Recording {
+    pub seed: u64,
+    /// every tick, including the ones without any input
+    pub ticks: Vec<TickInput>,
+}
+
+impl Recording {
+    /// reads a file written by `Recorder`. Replays are started on purpose, so a broken one
+    /// panics.
+    pub fn load(path: &str) -> Self {
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|err| panic!("can't open {}: {}", path, err));
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+
+        Self {
+            seed: parse_line(path, lines.next().unwrap_or_default()),
+            ticks: lines.map(|line| parse_line(path, line)).collect(),
+        }
+    }
+}
+
+fn parse_line<T: DeserializeOwned>(path: &str, line: &str) -> T {
+    ron::de::from_str(line).unwrap_or_else(|err| panic!("invalid {}: {}", path, err))
+}
+
+/// writes the input of every tick to a file while playing, started with `--record <file>`.
+/// Every game gets its own file, the ones after the first are numbered: `run.ron`, `run-2.ron`
+/// and so on.
+pub struct Recorder {
+    path: String,
+    file: BufWriter<File>,
+    /// counting the one being recorded
+    games: usize,
+    seed: u64,
+    ticks: usize,
+    /// ticks written since the last flush
+    unflushed: usize,
+}
+
+impl Recorder {
+    /// ticks between two flushes of the file, about once a second
+    const FLUSH_INTERVAL: usize = 60;
+
+    pub fn new(path: String, seed: u64) -> Self {
+        let file = Self::create(&path, seed);
+        Self {
+            path,
+            file,
+            games: 1,
+            seed,
+            ticks: 0,
+            unflushed: 0,
+        }
+    }
+
+    fn create(path: &str, seed: u64) -> BufWriter<File> {
+        let mut file = BufWriter::new(
+            File::create(path).unwrap_or_else(|err| panic!("can't create {}: {}", path, err)),
+        );
+        writeln!(file, "{}", seed).unwrap_or_else(|err| panic!("can't write {}: {}", path, err));
+        file
+    }
+
+    /// the file of the game being recorded
+    fn path(&self) -> String {
+        if self.games == 1 {
+            return self.path.clone();
+        }
+
+        let path = Path::new(&self.path);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => format!("{}-{}.{}", stem, self.games, extension.to_string_lossy()),
+            None => format!("{}-{}", stem, self.games),
+        };
+        path.with_file_name(name).to_string_lossy().into_owned()
+    }
+
+    pub fn record(&mut self, input: &TickInput) {
+        let line = ron::ser::to_string(input).unwrap();
+        writeln!(self.file, "{}", line)
+            .unwrap_or_else(|err| panic!("can't write {}: {}", self.path(), err));
+
+        self.ticks += 1;
+        self.unflushed += 1;
+        if self.unflushed == Self::FLUSH_INTERVAL {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.unflushed = 0;
+        self.file
+            .flush()
+            .unwrap_or_else(|err| panic!("can't write {}: {}", self.path(), err));
+    }
+
+    /// writes what's still buffered to the file, when the game ends.
+    pub fn save(&mut self) {
+        self.flush();
+        println!(
+            "recorded {} ticks of seed {} to {}",
+            self.ticks,
+            self.seed,
+            self.path()
+        );
+    }
+
+    /// finishes the file of the last game and starts the next one, when the game starts over.
+    pub fn start_over(&mut self, seed: u64) {
+        self.save();
+
+        self.games += 1;
+        self.seed = seed;
+        self.ticks = 0;
+        self.file = Self::create(&self.path(), seed);
+    }
+}
+
+/// plays a recording back tick by tick, started with `--replay <file>`.
+pub struct Replay {
+    recording: Recording,
+    tick: usize,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Self {
+        Self {
+            recording: Recording::load(path),
+            tick: 0,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.recording.seed
+    }
+
+    /// the input of the next tick, `None` once the recording is over
+    pub fn next_tick(&mut self) -> Option<&TickInput> {
+        let input = self.recording.ticks.get(self.tick);
+        self.tick += 1;
+        input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_generator::Level;
+    use crate::oxygen::OxygenOptions;
+    use crate::player::{Player, PlayerState};
+    use crate::simulation::Simulation;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn temp_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(name)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// records `ticks` to a file and loads it again
+    fn record(name: &str, seed: u64, ticks: &[TickInput]) -> Replay {
+        let path = temp_file(name);
+        let mut recorder = Recorder::new(path.clone(), seed);
+        for tick in ticks {
+            recorder.record(tick);
+        }
+        recorder.save();
+
+        let replay = Replay::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        replay
+    }
+
+    fn press(action: Action, pressed: bool) -> TickInput {
+        TickInput {
+            actions: vec![(action, pressed)],
+            ..TickInput::default()
+        }
+    }
+
+    /// plays the replay back from `start` through the same simulation as the game. The player
+    /// stays on the ground.
+    fn play(level: &Level, replay: &mut Replay, start: (f32, f32)) -> (Player, (f32, f32)) {
+        let oxygen = OxygenOptions {
+            depletion_rate: 0.0,
+            run_multiplier: 1.0,
+            tank_refill: 0.35,
+            tanks: (0, 0),
+            tank_prop: String::new(),
+        };
+
+        let mut simulation = Simulation::default();
+        let mut position = start;
+        while let Some(input) = replay.next_tick() {
+            simulation.apply(input);
+            position = simulation.step(level, position, true, &oxygen, DT);
+        }
+
+        (simulation.player, position)
+    }
+
+    #[test]
+    fn replay_ends_where_the_recording_did() {
+        let level = Level::parse(
+            "#######
+             #.....#
+             #.....#
+             #.....#
+             #######",
+        );
+
+        // three seconds forward into the wall, turn right and walk along it for half a second
+        let mut ticks = vec![TickInput::default(); 212];
+        ticks[0] = press(Action::MoveForward, true);
+        ticks[180] = TickInput {
+            actions: vec![(Action::MoveForward, false)],
+            look: (90.0, 0.0),
+            ..TickInput::default()
+        };
+        ticks[181] = press(Action::MoveForward, true);
+        ticks[211] = press(Action::MoveForward, false);
+
+        let mut replay = record("replay_ends_where_the_recording_did.ron", 42, &ticks);
+        assert_eq!(replay.seed(), 42);
+
+        let (player, (x, y)) = play(&level, &mut replay, (3.0, 1.0));
+        assert_eq!(player.state(), PlayerState::Standing);
+        let walked = 30.0 * DT * Player::SPEED;
+        assert!((x - (3.0 - walked)).abs() < 0.01, "x is {}", x);
+        assert!((y - (3.5 - Player::RADIUS)).abs() < 0.01, "y is {}", y);
+    }
+
+    #[test]
+    fn every_game_gets_its_own_file() {
+        let path = temp_file("every_game_gets_its_own_file.ron");
+        let mut recorder = Recorder::new(path.clone(), 1);
+        recorder.record(&press(Action::Jump, true));
+        recorder.record(&press(Action::Jump, false));
+        recorder.start_over(2);
+        recorder.record(&press(Action::Crouch, true));
+        let second = recorder.path();
+        recorder.save();
+
+        let first = Recording::load(&path);
+        assert_eq!((first.seed, first.ticks.len()), (1, 2));
+        assert_eq!(second, temp_file("every_game_gets_its_own_file-2.ron"));
+        let next = Recording::load(&second);
+        assert_eq!((next.seed, next.ticks.len()), (2, 1));
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&second).unwrap();
+    }
+}
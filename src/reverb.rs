@@ -0,0 +1,184 @@
+use serde::Deserialize;
+
+use crate::audio::AudioManager;
+
+/// how much a space echoes.
+#[derive(Deserialize, Copy, Clone, PartialEq, Debug)]
+pub struct ReverbParameters {
+    /// seconds until the echo died down
+    pub decay_time: f32,
+    /// how loud the echo is compared to the sound itself, from 0 to 1
+    pub wet: f32,
+}
+
+impl ReverbParameters {
+    /// what the station sounds like before the player entered any room
+    pub const DEFAULT: ReverbParameters = ReverbParameters {
+        decay_time: 0.8,
+        wet: 0.2,
+    };
+
+    /// the reverb of a room with `tiles` floor tiles, bigger rooms echo longer.
+    pub fn for_room(self, tiles: usize, options: &ReverbOptions) -> Self {
+        let scale = (tiles as f32 / options.reference_size).sqrt();
+        Self {
+            decay_time: (self.decay_time * scale)
+                .max(options.min_decay_time)
+                .min(options.max_decay_time),
+            wet: self.wet,
+        }
+    }
+}
+
+/// tuning of the reverb, part of `assets/level.ron`. Rooms get theirs from their theme.
+#[derive(Deserialize, Copy, Clone)]
+pub struct ReverbOptions {
+    /// all corridors are the same narrow metal tubes
+    pub corridor: ReverbParameters,
+    /// room size in tiles the reverb of the themes is meant for
+    pub reference_size: f32,
+    pub min_decay_time: f32,
+    pub max_decay_time: f32,
+    /// seconds it takes to switch over to the reverb of the next room
+    pub crossfade: f32,
+}
+
+/// blends the reverb of the sound context over to the one of the room the listener is in.
+pub struct RoomReverb {
+    current: ReverbParameters,
+    /// what `current` was when the fade to `target` started
+    start: ReverbParameters,
+    target: ReverbParameters,
+    /// from 0 to 1
+    progress: f32,
+}
+
+impl Default for RoomReverb {
+    fn default() -> Self {
+        Self {
+            current: ReverbParameters::DEFAULT,
+            start: ReverbParameters::DEFAULT,
+            target: ReverbParameters::DEFAULT,
+            progress: 1.0,
+        }
+    }
+}
+
+impl RoomReverb {
+    /// `zone` is the reverb where the listener is, `None` keeps the last one. Doors don't have
+    /// one, so walking through them fades from one side to the other.
+    pub fn update(
+        &mut self,
+        audio: &mut AudioManager,
+        zone: Option<ReverbParameters>,
+        options: &ReverbOptions,
+        dt: f32,
+    ) {
+        if let Some(current) = self.blend(zone, options, dt) {
+            audio.set_reverb(current);
+        }
+    }
+
+    /// moves the crossfade along, returns the reverb when it changed
+    fn blend(
+        &mut self,
+        zone: Option<ReverbParameters>,
+        options: &ReverbOptions,
+        dt: f32,
+    ) -> Option<ReverbParameters> {
+        if let Some(zone) = zone {
+            if zone != self.target {
+                self.start = self.current;
+                self.target = zone;
+                self.progress = 0.0;
+            }
+        }
+
+        if self.progress >= 1.0 {
+            return None;
+        }
+
+        self.progress = (self.progress + dt / options.crossfade.max(f32::EPSILON)).min(1.0);
+        let t = self.progress;
+        self.current = ReverbParameters {
+            decay_time: self.start.decay_time
+                + (self.target.decay_time - self.start.decay_time) * t,
+            wet: self.start.wet + (self.target.wet - self.start.wet) * t,
+        };
+        Some(self.current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> ReverbOptions {
+        ReverbOptions {
+            corridor: ReverbParameters {
+                decay_time: 0.4,
+                wet: 0.1,
+            },
+            reference_size: 25.0,
+            min_decay_time: 0.2,
+            max_decay_time: 4.0,
+            crossfade: 1.0,
+        }
+    }
+
+    const HALL: ReverbParameters = ReverbParameters {
+        decay_time: 2.0,
+        wet: 0.5,
+    };
+
+    #[test]
+    fn decay_grows_with_the_square_root_of_the_room() {
+        let options = options();
+        assert_eq!(HALL.for_room(25, &options), HALL);
+
+        let bigger = HALL.for_room(100, &options);
+        assert!((bigger.decay_time - 4.0).abs() < 0.001);
+        assert_eq!(bigger.wet, HALL.wet);
+
+        let smaller = HALL.for_room(4, &options);
+        assert!((smaller.decay_time - 0.8).abs() < 0.001);
+    }
+
+    #[test]
+    fn decay_stays_within_its_limits() {
+        let options = options();
+        assert_eq!(
+            HALL.for_room(400, &options).decay_time,
+            options.max_decay_time
+        );
+        assert_eq!(
+            HALL.for_room(0, &options).decay_time,
+            options.min_decay_time
+        );
+    }
+
+    #[test]
+    fn crossfade_blends_over_to_the_next_room() {
+        let options = options();
+        let mut reverb = RoomReverb::default();
+
+        let halfway = reverb.blend(Some(HALL), &options, 0.5).unwrap();
+        assert!((halfway.decay_time - 1.4).abs() < 0.001);
+        assert!((halfway.wet - 0.35).abs() < 0.001);
+
+        assert_eq!(reverb.blend(Some(HALL), &options, 0.5), Some(HALL));
+        assert_eq!(reverb.blend(Some(HALL), &options, 0.5), None);
+    }
+
+    #[test]
+    fn doors_and_unloaded_tiles_keep_the_last_room() {
+        let options = options();
+        let mut reverb = RoomReverb::default();
+        reverb.blend(Some(HALL), &options, 0.5);
+
+        // the fade carries on towards the room the listener came from
+        assert_eq!(reverb.blend(None, &options, 0.5), Some(HALL));
+        assert_eq!(reverb.blend(None, &options, 0.5), None);
+        assert_eq!(reverb.current, HALL);
+    }
+}
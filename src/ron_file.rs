@@ -0,0 +1,11 @@
+use std::fs::File;
+
+use serde::de::DeserializeOwned;
+
+/// reads a data file of the game. They ship with it, so a missing or broken one is a bug and
+/// panics.
+pub fn load_ron<T: DeserializeOwned>(path: &str) -> T {
+    let file = File::open(path).unwrap_or_else(|err| panic!("can't open {}: {}", path, err));
+
+    ron::de::from_reader(file).unwrap_or_else(|err| panic!("invalid {}: {}", path, err))
+}
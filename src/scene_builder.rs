@@ -0,0 +1,921 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rg3d::core::color::Color;
+use rg3d::core::pool::Handle;
+use rg3d::engine::resource_manager::{ResourceManager, SharedSoundBuffer};
+use rg3d::physics::dynamics::RigidBodyHandle;
+use rg3d::physics::geometry::{ColliderBuilder, ColliderHandle};
+use rg3d::physics::na::{Matrix4, UnitQuaternion, Vector3};
+use rg3d::resource::model::Model;
+use rg3d::scene::base::BaseBuilder;
+use rg3d::scene::light::{BaseLightBuilder, PointLightBuilder};
+use rg3d::scene::mesh::surface::{Surface, SurfaceSharedData};
+use rg3d::scene::mesh::MeshBuilder;
+use rg3d::scene::node::Node;
+use rg3d::scene::transform::TransformBuilder;
+use rg3d::scene::Scene;
+use rg3d::sound::source::SoundSource;
+
+use crate::audio::AudioManager;
+use crate::character_controller::add_level_colliders;
+use crate::collision::CollisionGrid;
+use crate::interaction::{Action, Door, Interactable};
+use crate::inventory::Item;
+use crate::level_config::LevelConfig;
+use crate::level_generator::{FieldType, Level};
+use crate::lighting::{plan_lights, FlickeringLight, LightRole, LightingOptions};
+use crate::oxygen::OxygenOptions;
+use crate::props::{candidates, load_props, place_props, Placement, PropDefinition, Side};
+use crate::reverb::ReverbParameters;
+use crate::ron_file::load_ron;
+use crate::sound::{add_prop_sound, add_room_ambience};
+use crate::theme::{assign_themes, load_themes, Theme};
+
+/// all resources needed to turn a `Level` into scene nodes. They are loaded once, so chunks can
+/// be built synchronously while the game is running.
+pub struct SceneAssets {
+    wall: Model,
+    corridor: Model,
+    wall_inner_corner: Model,
+    wall_outer_corner: Model,
+    themes: Vec<Theme>,
+    theme_assets: Vec<ThemeAssets>,
+    props: HashMap<String, PropAssets>,
+    /// texts shown on the terminals, see `assets/terminals.ron`
+    terminal_messages: Vec<String>,
+}
+
+struct PropAssets {
+    definition: PropDefinition,
+    model: Model,
+    sound: Option<SharedSoundBuffer>,
+}
+
+struct ThemeAssets {
+    floor: Model,
+    wall: Model,
+    ambient_sound: Option<SharedSoundBuffer>,
+}
+
+impl SceneAssets {
+    pub async fn load(resource_manager: &ResourceManager) -> Self {
+        let mut props = HashMap::new();
+        for definition in load_props("assets/props.ron") {
+            let sound = match &definition.sound {
+                Some(sound) => Some(
+                    resource_manager
+                        .request_sound_buffer(&sound.path, false)
+                        .await
+                        .unwrap(),
+                ),
+                None => None,
+            };
+
+            props.insert(
+                definition.name.clone(),
+                PropAssets {
+                    model: load_model(resource_manager, &definition.model).await,
+                    sound,
+                    definition,
+                },
+            );
+        }
+
+        let themes = load_themes("assets/themes.ron");
+
+        let mut theme_assets = Vec::new();
+        for theme in &themes {
+            if let Some(prop) = theme.props.iter().find(|&p| !props.contains_key(p)) {
+                panic!("theme {} uses unknown prop {}!", theme.name, prop);
+            }
+
+            let ambient_sound = match &theme.ambient_sound {
+                Some(path) => Some(
+                    resource_manager
+                        .request_sound_buffer(path, false)
+                        .await
+                        .unwrap(),
+                ),
+                None => None,
+            };
+
+            theme_assets.push(ThemeAssets {
+                floor: load_model(resource_manager, &theme.floor).await,
+                wall: load_model(resource_manager, &theme.wall).await,
+                ambient_sound,
+            });
+        }
+
+        Self {
+            wall: load_model(resource_manager, "assets/wall.fbx").await,
+            corridor: load_model(resource_manager, "assets/corridor.fbx").await,
+            wall_inner_corner: load_model(resource_manager, "assets/wall_inner_corner.fbx").await,
+            wall_outer_corner: load_model(resource_manager, "assets/wall_outer_corner.fbx").await,
+            themes,
+            theme_assets,
+            props,
+            terminal_messages: load_ron("assets/terminals.ron"),
+        }
+    }
+}
+
+async fn load_model(resource_manager: &ResourceManager, path: &str) -> Model {
+    resource_manager.request_model(path).await.unwrap()
+}
+
+/// everything that was added to the scene and the sound context for one level. Removing it
+/// again must go through `remove`, otherwise the sounds keep playing.
+pub struct LevelContent {
+    pub root: Handle<Node>,
+    /// static body holding the colliders of the floor and the walls
+    pub body: RigidBodyHandle,
+    pub sounds: Vec<Handle<SoundSource>>,
+    /// echo of each room, from its theme and size
+    pub room_reverbs: Vec<ReverbParameters>,
+    pub flickering_lights: Vec<FlickeringLight>,
+    /// all lights of the level, they share the shadow budget with the other chunks
+    pub lights: Vec<Handle<Node>>,
+    /// everything the player can use: oxygen tanks, doors, terminals and light switches
+    pub interactables: Vec<Interactable>,
+}
+
+impl LevelContent {
+    /// removes everything from the scene. The sounds fade out instead of stopping dead.
+    pub fn remove(self, scene: &mut Scene, audio: &mut AudioManager) {
+        scene.remove_node(self.root);
+        scene.physics.bodies.remove(
+            self.body,
+            &mut scene.physics.colliders,
+            &mut scene.physics.joints,
+        );
+        for sound in self.sounds {
+            audio.fade_out(sound, 0.5);
+        }
+    }
+}
+
+pub fn create_point_light(radius: f32, color: Color, cast_shadows: bool) -> Node {
+    let point_light = PointLightBuilder::new(
+        BaseLightBuilder::new(BaseBuilder::new())
+            .with_color(color)
+            .cast_shadows(cast_shadows),
+    );
+
+    point_light.with_radius(radius).build_node()
+}
+
+/// a plain box for things there is no model for. `size` is the full size of the box, which is
+/// centered on the node.
+pub fn create_box(size: Vector3<f32>, color: Color) -> Node {
+    let mut surface = Surface::new(Arc::new(RwLock::new(SurfaceSharedData::make_cube(
+        Matrix4::new_nonuniform_scaling(&size),
+    ))));
+    surface.set_color(color);
+
+    MeshBuilder::new(BaseBuilder::new())
+        .with_surfaces(vec![surface])
+        .build_node()
+}
+
+/// builds the level below a new root node placed at `origin`. All tiles are positioned relative
+/// to that root, so `(x, y)` of the map ends up at `origin + (x, 0, y)` in the world.
+pub fn build_level(
+    level: &mut Level,
+    scene: &mut Scene,
+    audio: &mut AudioManager,
+    assets: &SceneAssets,
+    config: &LevelConfig,
+    origin: Vector3<f32>,
+    rng: &mut StdRng,
+) -> LevelContent {
+    let root = scene.graph.add_node(Node::Base(
+        BaseBuilder::new()
+            .with_local_transform(TransformBuilder::new().with_local_position(origin).build())
+            .build(),
+    ));
+
+    let room_themes = assign_themes(&assets.themes, level.rooms.len(), rng);
+
+    // theme of every room tile, corridors and doors don't have one
+    let mut tile_themes = HashMap::new();
+    for (room, &theme) in level.rooms.iter().zip(room_themes.iter()) {
+        for &tile in room {
+            tile_themes.insert(tile, theme);
+        }
+    }
+
+    add_corners(level, scene, assets, root);
+    add_rest(level, scene, assets, &tile_themes, root);
+
+    let (sounds, mut interactables) = dress_rooms(
+        level,
+        scene,
+        audio,
+        assets,
+        &room_themes,
+        config,
+        root,
+        origin,
+        rng,
+    );
+
+    let (lights, flickering_lights, room_lights) = add_lights(
+        level,
+        scene,
+        assets,
+        &room_themes,
+        &config.lighting,
+        root,
+        rng,
+    );
+
+    interactables.extend(add_light_switches(
+        level,
+        scene,
+        room_lights,
+        root,
+        origin,
+        rng,
+    ));
+
+    let body = add_level_colliders(scene, origin, level.width(), level.height(), |x, y| {
+        level.is_solid((x, y))
+    });
+
+    interactables.extend(add_doors(level, scene, body, root, origin));
+
+    let room_reverbs = level
+        .rooms
+        .iter()
+        .zip(room_themes.iter())
+        .map(|(room, &theme)| {
+            assets.themes[theme]
+                .reverb
+                .for_room(room.len(), &config.reverb)
+        })
+        .collect();
+
+    LevelContent {
+        root,
+        body,
+        sounds,
+        room_reverbs,
+        flickering_lights,
+        lights,
+        interactables,
+    }
+}
+
+fn instantiate(scene: &mut Scene, model: &Model, root: Handle<Node>) -> Handle<Node> {
+    let handle = model.instantiate_geometry(scene);
+    scene.graph.link_nodes(handle, root);
+    handle
+}
+
+fn add_corners(level: &mut Level, scene: &mut Scene, assets: &SceneAssets, root: Handle<Node>) {
+    for x in 0..level.map.len() {
+        for y in 0..level.map[0].len() {
+            if level.map[x][y].typ == FieldType::Empty {
+                // add outer corners
+                let neighbours = level
+                    .get_neighbours((x, y), 1)
+                    .into_iter()
+                    .filter(|&(x, y)| level.map[x][y].typ != FieldType::Empty)
+                    .collect::<Vec<_>>();
+
+                let mut add_corner = |rotation: f32| {
+                    let corner_handle = instantiate(scene, &assets.wall_outer_corner, root);
+                    scene.graph[corner_handle]
+                        .local_transform_mut()
+                        .set_rotation(UnitQuaternion::from_axis_angle(
+                            &Vector3::y_axis(),
+                            rotation.to_radians(),
+                        ))
+                        .offset(Vector3::new(x as f32, 0.0, y as f32));
+                };
+
+                if neighbours.iter().any(|&(n_x, _)| n_x < x)
+                    && neighbours.iter().any(|&(_, n_y)| n_y < y)
+                {
+                    add_corner(0.0);
+                    level.map[x - 1][y].walls.right_up = true;
+                    level.map[x][y - 1].walls.down_left = true;
+                }
+
+                if neighbours.iter().any(|&(n_x, _)| n_x < x)
+                    && neighbours.iter().any(|&(_, n_y)| n_y > y)
+                {
+                    add_corner(90.0);
+                    level.map[x - 1][y].walls.right_down = true;
+                    level.map[x][y + 1].walls.up_left = true;
+                }
+
+                if neighbours.iter().any(|&(n_x, _)| n_x > x)
+                    && neighbours.iter().any(|&(_, n_y)| n_y < y)
+                {
+                    add_corner(-90.0);
+                    level.map[x + 1][y].walls.left_up = true;
+                    level.map[x][y - 1].walls.down_right = true;
+                }
+
+                if neighbours.iter().any(|&(n_x, _)| n_x > x)
+                    && neighbours.iter().any(|&(_, n_y)| n_y > y)
+                {
+                    add_corner(180.0);
+                    level.map[x + 1][y].walls.left_down = true;
+                    level.map[x][y + 1].walls.up_right = true;
+                }
+
+                continue;
+            }
+
+            // add inner corners
+            let neighbours = level
+                .get_neighbours((x, y), 1)
+                .into_iter()
+                .filter(|&(x, y)| level.map[x][y].typ == FieldType::Empty)
+                .collect::<Vec<_>>();
+
+            let mut add_corner = |rotation: f32| {
+                let corner_handle = instantiate(scene, &assets.wall_inner_corner, root);
+                scene.graph[corner_handle]
+                    .local_transform_mut()
+                    .set_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::y_axis(),
+                        rotation.to_radians(),
+                    ))
+                    .offset(Vector3::new(x as f32, 0.0, y as f32));
+            };
+
+            if neighbours.iter().any(|&(n_x, _)| n_x < x)
+                && neighbours.iter().any(|&(_, n_y)| n_y < y)
+            {
+                level.map[x][y].walls.up_left = true;
+                level.map[x][y].walls.left_up = true;
+                add_corner(0.0);
+            }
+
+            if neighbours.iter().any(|&(n_x, _)| n_x > x)
+                && neighbours.iter().any(|&(_, n_y)| n_y < y)
+            {
+                level.map[x][y].walls.up_right = true;
+                level.map[x][y].walls.right_up = true;
+                add_corner(-90.0);
+            }
+
+            if neighbours.iter().any(|&(n_x, _)| n_x < x)
+                && neighbours.iter().any(|&(_, n_y)| n_y > y)
+            {
+                level.map[x][y].walls.down_left = true;
+                level.map[x][y].walls.left_down = true;
+                add_corner(90.0);
+            }
+
+            if neighbours.iter().any(|&(n_x, _)| n_x > x)
+                && neighbours.iter().any(|&(_, n_y)| n_y > y)
+            {
+                level.map[x][y].walls.down_right = true;
+                level.map[x][y].walls.right_down = true;
+                add_corner(180.0);
+            }
+        }
+    }
+}
+
+fn add_rest(
+    level: &mut Level,
+    scene: &mut Scene,
+    assets: &SceneAssets,
+    tile_themes: &HashMap<(usize, usize), usize>,
+    root: Handle<Node>,
+) {
+    for x in 0..level.map.len() {
+        for y in 0..level.map[0].len() {
+            if level.map[x][y].typ == FieldType::Empty {
+                continue;
+            }
+
+            let theme = tile_themes.get(&(x, y)).map(|&i| &assets.theme_assets[i]);
+
+            // create floor
+            let floor_handle = match (level.map[x][y].typ, theme) {
+                (FieldType::Floor, Some(theme)) => instantiate(scene, &theme.floor, root),
+                _ => instantiate(scene, &assets.corridor, root),
+            };
+
+            scene.graph[floor_handle]
+                .local_transform_mut()
+                .offset(Vector3::new(x as f32, 0.0, y as f32));
+
+            // fill in missing walls
+            let wall = theme.map_or(&assets.wall, |theme| &theme.wall);
+            let add_wall = |scene: &mut Scene, rotation: f32, offset_x: f32, offset_y: f32| {
+                let wall_handle = instantiate(scene, wall, root);
+                scene.graph[wall_handle]
+                    .local_transform_mut()
+                    .set_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::y_axis(),
+                        rotation.to_radians(),
+                    ))
+                    .offset(Vector3::new(x as f32 + offset_x, 0.0, y as f32 + offset_y));
+            };
+
+            let neighbours = level
+                .get_neighbours((x, y), 1)
+                .into_iter()
+                .filter(|(x, y)| level.map[*x][*y].typ == FieldType::Empty)
+                .collect::<Vec<_>>();
+
+            for n in neighbours {
+                let mut walls = &mut level.map[x][y].walls;
+                if n.0 < x {
+                    if !walls.left_up {
+                        add_wall(scene, 90.0, 0.0, -0.5);
+                        walls.left_up = true;
+                    }
+                    if !walls.left_down {
+                        add_wall(scene, 90.0, 0.0, 0.0);
+                        walls.left_down = true;
+                    }
+                }
+                if n.0 > x {
+                    if !walls.right_up {
+                        add_wall(scene, -90.0, 0.0, 0.0);
+                        walls.right_up = true;
+                    }
+                    if !walls.right_down {
+                        add_wall(scene, -90.0, 0.0, 0.5);
+                        walls.right_down = true;
+                    }
+                }
+                if n.1 < y {
+                    if !walls.up_left {
+                        add_wall(scene, 0.0, 0.0, 0.0);
+                        walls.up_left = true;
+                    }
+                    if !walls.up_right {
+                        add_wall(scene, 0.0, 0.5, 0.0);
+                        walls.up_right = true;
+                    }
+                }
+                if n.1 > y {
+                    if !walls.down_left {
+                        add_wall(scene, 180.0, -0.5, 0.0);
+                        walls.down_left = true;
+                    }
+                    if !walls.down_right {
+                        add_wall(scene, 180.0, 0.0, 0.0);
+                        walls.down_right = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// adds ambient sounds and props to the rooms according to their theme and spreads the oxygen
+/// tanks, batteries and terminals over the rooms. Returns the handles of all sounds that were
+/// added and the pickups and terminals.
+#[allow(clippy::too_many_arguments)]
+fn dress_rooms(
+    level: &mut Level,
+    scene: &mut Scene,
+    audio: &mut AudioManager,
+    assets: &SceneAssets,
+    room_themes: &[usize],
+    config: &LevelConfig,
+    root: Handle<Node>,
+    origin: Vector3<f32>,
+    rng: &mut StdRng,
+) -> (Vec<Handle<SoundSource>>, Vec<Interactable>) {
+    let mut sounds = Vec::new();
+    let mut interactables = Vec::new();
+    // ids of the pickups, see `Action::Pickup`
+    let mut pickup_count = 0;
+
+    for room in &mut level.rooms {
+        room.sort();
+    }
+
+    let oxygen = &config.oxygen;
+    let tank = assets
+        .props
+        .get(&oxygen.tank_prop)
+        .unwrap_or_else(|| panic!("unknown oxygen tank prop {}!", oxygen.tank_prop));
+
+    let room_tanks = spread_over_rooms(level.rooms.len(), oxygen.tanks, rng);
+    let room_batteries = spread_over_rooms(level.rooms.len(), config.flashlight.batteries, rng);
+
+    for (((room, &theme_index), &room_tank_count), &room_battery_count) in level
+        .rooms
+        .iter()
+        .zip(room_themes.iter())
+        .zip(room_tanks.iter())
+        .zip(room_batteries.iter())
+    {
+        let theme = &assets.themes[theme_index];
+        let theme_assets = &assets.theme_assets[theme_index];
+
+        let pos = room[room.len() / 2];
+
+        let (min_x, min_y) = room[0];
+        let (max_x, max_y) = room[room.len() - 1];
+
+        if let Some(ambient_sound) = &theme_assets.ambient_sound {
+            let radius = (max_x - min_x).max(max_y - min_y) as f32;
+            sounds.push(add_room_ambience(
+                audio,
+                ambient_sound.clone(),
+                theme.ambient_gain,
+                origin.x + pos.0 as f32,
+                origin.z + pos.1 as f32,
+                radius,
+            ));
+        }
+
+        let mut placed = Vec::new();
+
+        for prop in &theme.props {
+            let prop = &assets.props[prop];
+
+            for placement in place_props(level, room, &prop.definition, &mut placed, rng) {
+                let (x, y) = placement.tile;
+
+                // a tank that can't be picked up would look exactly like one that can
+                if prop.definition.oxygen_tank {
+                    interactables.push(add_oxygen_tank(
+                        scene,
+                        &prop.model,
+                        root,
+                        origin,
+                        Vector3::new(x as f32, 0.0, y as f32),
+                        placement.rotation,
+                        Some(pickup_count),
+                    ));
+                    pickup_count += 1;
+                    continue;
+                }
+
+                let handle = instantiate(scene, &prop.model, root);
+                scene.graph[handle]
+                    .local_transform_mut()
+                    .offset(Vector3::new(x as f32, 0.0, y as f32))
+                    .set_rotation(UnitQuaternion::from_axis_angle(
+                        &Vector3::y_axis(),
+                        placement.rotation.to_radians(),
+                    ));
+
+                if let (Some(buffer), Some(sound)) = (&prop.sound, &prop.definition.sound) {
+                    // props against a wall make their noise from the wall, not from the middle
+                    // of the tile
+                    let (offset_x, offset_y) = placement.wall.map_or((0.0, 0.0), |w| w.offset());
+
+                    // sound sources don't live in the scene graph, so they need world
+                    // coordinates
+                    sounds.push(add_prop_sound(
+                        audio,
+                        buffer.clone(),
+                        sound.gain,
+                        origin.x + x as f32 + offset_x,
+                        origin.z + y as f32 + offset_y,
+                    ));
+                }
+            }
+        }
+
+        // the tanks of the level come last, the props of the theme take precedence
+        for _ in 0..room_tank_count {
+            for placement in place_props(level, room, &tank.definition, &mut placed, rng) {
+                let (x, y) = placement.tile;
+
+                interactables.push(add_oxygen_tank(
+                    scene,
+                    &tank.model,
+                    root,
+                    origin,
+                    Vector3::new(x as f32, 0.0, y as f32),
+                    placement.rotation,
+                    Some(pickup_count),
+                ));
+                pickup_count += 1;
+            }
+        }
+
+        for _ in 0..room_battery_count {
+            let free = candidates(level, room, Placement::Anywhere)
+                .into_iter()
+                .filter(|(tile, _)| !placed.contains(tile))
+                .collect::<Vec<_>>();
+
+            if let Some(&((x, y), _)) = free.choose(rng) {
+                placed.push((x, y));
+
+                let size = Vector3::new(0.06, 0.12, 0.06);
+                let node = scene
+                    .graph
+                    .add_node(create_box(size, Color::opaque(230, 190, 40)));
+                scene.graph.link_nodes(node, root);
+                let position = Vector3::new(x as f32, size.y / 2.0, y as f32);
+                scene.graph[node]
+                    .local_transform_mut()
+                    .set_position(position);
+
+                interactables.push(Interactable {
+                    node,
+                    position: origin + position,
+                    action: Action::Pickup {
+                        item: Item::Battery,
+                        id: Some(pickup_count),
+                    },
+                });
+                pickup_count += 1;
+            }
+        }
+
+        if !assets.terminal_messages.is_empty() && rng.gen_bool(TERMINAL_CHANCE) {
+            let free = candidates(level, room, Placement::AgainstWall)
+                .into_iter()
+                .filter(|(tile, _)| !placed.contains(tile))
+                .collect::<Vec<_>>();
+
+            if let Some(&(tile, Some(side))) = free.choose(rng) {
+                placed.push(tile);
+
+                let size = Vector3::new(0.5, 0.6, 0.25);
+                let node = add_wall_box(scene, tile, side, size, 0.3, Color::opaque(60, 65, 70));
+                scene.graph.link_nodes(node, root);
+
+                interactables.push(Interactable {
+                    node,
+                    position: origin + scene.graph[node].local_transform().position(),
+                    action: Action::Terminal {
+                        message: assets.terminal_messages.choose(rng).unwrap().clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    (sounds, interactables)
+}
+
+impl SceneAssets {
+    /// places an oxygen tank dropped by the player at the world `position`. `root` is the root
+    /// node of the chunk at `origin`.
+    pub fn add_dropped_oxygen_tank(
+        &self,
+        scene: &mut Scene,
+        oxygen: &OxygenOptions,
+        root: Handle<Node>,
+        origin: Vector3<f32>,
+        position: Vector3<f32>,
+    ) -> Interactable {
+        let tank = &self.props[&oxygen.tank_prop];
+        add_oxygen_tank(
+            scene,
+            &tank.model,
+            root,
+            origin,
+            position - origin,
+            0.0,
+            None,
+        )
+    }
+}
+
+/// `position` is relative to `root`, which is placed at `origin`. `id` is `None` for tanks
+/// dropped by the player.
+fn add_oxygen_tank(
+    scene: &mut Scene,
+    model: &Model,
+    root: Handle<Node>,
+    origin: Vector3<f32>,
+    position: Vector3<f32>,
+    rotation: f32,
+    id: Option<usize>,
+) -> Interactable {
+    let node = instantiate(scene, model, root);
+    scene.graph[node]
+        .local_transform_mut()
+        .offset(position)
+        .set_rotation(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            rotation.to_radians(),
+        ));
+
+    Interactable {
+        node,
+        position: origin + position + Vector3::new(0.0, TANK_HEIGHT, 0.0),
+        action: Action::Pickup {
+            item: Item::OxygenTank,
+            id,
+        },
+    }
+}
+
+/// rolls how many things to place within the inclusive `range` and picks a random room for each
+/// of them. Returns the number of things for every room.
+fn spread_over_rooms(room_count: usize, range: (usize, usize), rng: &mut StdRng) -> Vec<usize> {
+    let mut counts = vec![0; room_count];
+    if room_count > 0 {
+        for _ in 0..rng.gen_range(range.0, range.1 + 1) {
+            counts[rng.gen_range(0, room_count)] += 1;
+        }
+    }
+    counts
+}
+
+/// chance of a room having a terminal
+const TERMINAL_CHANCE: f64 = 0.3;
+
+/// height of the part of an oxygen tank the player has to look at to pick it up
+const TANK_HEIGHT: f32 = 0.3;
+
+/// creates a box standing with its back against the `side` wall of `tile`, with its center at
+/// `height` above the floor. The node isn't linked to any parent yet.
+fn add_wall_box(
+    scene: &mut Scene,
+    (x, y): (usize, usize),
+    side: Side,
+    size: Vector3<f32>,
+    height: f32,
+    color: Color,
+) -> Handle<Node> {
+    let node = scene.graph.add_node(create_box(size, color));
+
+    // the depth of the box is along z, the rotation turns it towards the wall
+    let (offset_x, offset_y) = side.offset();
+    scene.graph[node]
+        .local_transform_mut()
+        .set_position(Vector3::new(
+            x as f32 + offset_x * (1.0 - size.z),
+            height,
+            y as f32 + offset_y * (1.0 - size.z),
+        ))
+        .set_rotation(UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            side.rotation().to_radians(),
+        ));
+
+    node
+}
+
+/// puts a switch for the lights of every lit room on one of its walls.
+fn add_light_switches(
+    level: &Level,
+    scene: &mut Scene,
+    room_lights: Vec<Vec<Handle<Node>>>,
+    root: Handle<Node>,
+    origin: Vector3<f32>,
+    rng: &mut StdRng,
+) -> Vec<Interactable> {
+    let mut switches = Vec::new();
+
+    for (room, lights) in level.rooms.iter().zip(room_lights) {
+        if lights.is_empty() {
+            continue;
+        }
+
+        let walls = candidates(level, room, Placement::AgainstWall);
+        if let Some(&(tile, Some(side))) = walls.choose(rng) {
+            let size = Vector3::new(0.08, 0.12, 0.03);
+            let node = add_wall_box(scene, tile, side, size, 1.2, Color::opaque(200, 200, 190));
+            scene.graph.link_nodes(node, root);
+
+            switches.push(Interactable {
+                node,
+                position: origin + scene.graph[node].local_transform().position(),
+                action: Action::LightSwitch { lights, on: true },
+            });
+        }
+    }
+
+    switches
+}
+
+/// closes every door tile with a sliding door. The doors block the doorway with a collider in
+/// the static `body` of the level until they are opened.
+fn add_doors(
+    level: &Level,
+    scene: &mut Scene,
+    body: RigidBodyHandle,
+    root: Handle<Node>,
+    origin: Vector3<f32>,
+) -> Vec<Interactable> {
+    let mut doors = Vec::new();
+
+    for x in 0..level.width() {
+        for y in 0..level.height() {
+            if level.map[x][y].typ != FieldType::Door {
+                continue;
+            }
+
+            // the door spans the tile across the direction the doorway leads
+            let along_x = !level.is_solid((x as i32 - 1, y as i32))
+                && !level.is_solid((x as i32 + 1, y as i32));
+            let size = if along_x {
+                Vector3::new(0.1, 2.0, 1.0)
+            } else {
+                Vector3::new(1.0, 2.0, 0.1)
+            };
+
+            let node = scene
+                .graph
+                .add_node(create_box(size, Color::opaque(120, 125, 130)));
+            scene.graph.link_nodes(node, root);
+            scene.graph[node]
+                .local_transform_mut()
+                .set_position(Vector3::new(x as f32, size.y / 2.0, y as f32));
+
+            let collider = add_door_collider(scene, body, (x, y), size);
+
+            doors.push(Interactable {
+                node,
+                position: origin + Vector3::new(x as f32, 1.2, y as f32),
+                action: Action::Door(Door {
+                    tile: (x, y),
+                    size,
+                    collider: Some(collider),
+                }),
+            });
+        }
+    }
+
+    doors
+}
+
+/// `body` is the static body of the level, `tile` is relative to it.
+pub fn add_door_collider(
+    scene: &mut Scene,
+    body: RigidBodyHandle,
+    (x, y): (usize, usize),
+    size: Vector3<f32>,
+) -> ColliderHandle {
+    scene.physics.colliders.insert(
+        ColliderBuilder::cuboid(size.x / 2.0, size.y / 2.0, size.z / 2.0)
+            .translation(x as f32, size.y / 2.0, y as f32)
+            .build(),
+        body,
+        &mut scene.physics.bodies,
+    )
+}
+
+/// adds the lights planned by `plan_lights`. Returns the broken ones, they need to be updated
+/// every frame, and the lights of every room.
+fn add_lights(
+    level: &Level,
+    scene: &mut Scene,
+    assets: &SceneAssets,
+    room_themes: &[usize],
+    lighting: &LightingOptions,
+    root: Handle<Node>,
+    rng: &mut StdRng,
+) -> (
+    Vec<Handle<Node>>,
+    Vec<FlickeringLight>,
+    Vec<Vec<Handle<Node>>>,
+) {
+    let mut lights = Vec::new();
+    let mut flickering_lights = Vec::new();
+    let mut room_lights = vec![Vec::new(); level.rooms.len()];
+
+    for light in plan_lights(level, lighting, rng) {
+        let color = match (light.role, light.room) {
+            (LightRole::Emergency, _) => Color::opaque(255, 40, 30),
+            (_, Some(room)) => {
+                let (r, g, b) = assets.themes[room_themes[room]].light_color;
+                Color::opaque(r, g, b)
+            }
+            (_, None) => Color::WHITE,
+        };
+
+        // shadows are handed out by `World::update_lights`
+        let handle = scene
+            .graph
+            .add_node(create_point_light(light.radius, color, false));
+        scene.graph.link_nodes(handle, root);
+        lights.push(handle);
+
+        scene.graph[handle]
+            .local_transform_mut()
+            .set_position(Vector3::new(
+                light.position.0,
+                light.height,
+                light.position.1,
+            ));
+
+        if light.role == LightRole::Flickering {
+            flickering_lights.push(FlickeringLight::new(handle, rng.gen()));
+        }
+
+        if let Some(room) = light.room {
+            room_lights[room].push(handle);
+        }
+    }
+
+    (lights, flickering_lights, room_lights)
+}
@@ -0,0 +1,152 @@
+use std::fs::File;
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::KeyBindings;
+
+/// user settings, stored in `settings.ron` next to the executable's working directory. Missing
+/// values are filled in with their defaults, so old files keep working.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub head_bob: HeadBobSettings,
+    pub footsteps: FootstepSettings,
+    pub look: LookSettings,
+    pub audio: AudioSettings,
+    pub key_bindings: KeyBindings,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct HeadBobSettings {
+    /// turn this off if the bobbing makes you feel sick
+    pub enabled: bool,
+    /// how far the camera moves up and down per step, in units
+    pub vertical_amplitude: f32,
+    /// how far the camera moves sideways per step, in units
+    pub sway_amplitude: f32,
+    /// all amplitudes are multiplied by this while running
+    pub run_multiplier: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FootstepSettings {
+    /// distance between two steps while walking, in units
+    pub walk_stride: f32,
+    /// distance between two steps while running, in units
+    pub run_stride: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LookSettings {
+    /// degrees the camera turns per unit of mouse movement, sideways
+    pub sensitivity_x: f32,
+    /// degrees the camera turns per unit of mouse movement, up and down
+    pub sensitivity_y: f32,
+    /// moving the mouse up looks down
+    pub invert_y: bool,
+    /// from 0 (off) to 0.9, how much of the last movement carries over into the next tick.
+    /// Higher values feel smoother but lag behind.
+    pub smoothing: f32,
+    /// 0 turns it off. Fast mouse movements turn the camera farther than slow ones, by this
+    /// much per 1000 units of movement per second.
+    pub acceleration: f32,
+    /// vertical field of view, in degrees
+    pub fov: f32,
+}
+
+/// volumes of the buses of the `AudioManager`
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: BusSettings,
+    pub ambient: BusSettings,
+    pub sfx: BusSettings,
+    pub voice: BusSettings,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct BusSettings {
+    /// from 0 to 1
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for HeadBobSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            vertical_amplitude: 0.015,
+            sway_amplitude: 0.01,
+            run_multiplier: 1.6,
+        }
+    }
+}
+
+impl Default for LookSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity_x: 0.15,
+            sensitivity_y: 0.15,
+            invert_y: false,
+            smoothing: 0.0,
+            acceleration: 0.0,
+            fov: 75.0,
+        }
+    }
+}
+
+impl Default for BusSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl Default for FootstepSettings {
+    fn default() -> Self {
+        Self {
+            walk_stride: 0.58,
+            run_stride: 0.79,
+        }
+    }
+}
+
+impl Settings {
+    pub const PATH: &'static str = "settings.ron";
+
+    /// loads the settings. Creates the file with the default settings if it doesn't exist yet,
+    /// so players have something to edit.
+    pub fn load() -> Self {
+        match File::open(Self::PATH) {
+            Ok(file) => {
+                let mut settings: Settings = ron::de::from_reader(file).unwrap_or_else(|err| {
+                    println!("invalid {}, using the defaults: {}", Self::PATH, err);
+                    Settings::default()
+                });
+                // show new actions in the file, so they can be rebound
+                if settings.key_bindings.add_missing() {
+                    settings.save();
+                }
+                settings
+            }
+            Err(_) => {
+                let settings = Settings::default();
+                settings.save();
+                settings
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).unwrap();
+        if let Err(err) = std::fs::write(Self::PATH, text) {
+            println!("can't save {}: {}", Self::PATH, err);
+        }
+    }
+}
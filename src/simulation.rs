@@ -0,0 +1,159 @@
+use crate::collision::CollisionGrid;
+use crate::input::{Action, InputController};
+use crate::mouse_look::MouseLook;
+use crate::movement::walk;
+use crate::oxygen::OxygenOptions;
+use crate::player::Player;
+use crate::replay::TickInput;
+
+/// everything a tick changes that doesn't need the engine: the player, the state of the
+/// controls and where the camera looks. The game runs every tick through it, so replays can be
+/// checked without a window.
+#[derive(Default)]
+pub struct Simulation {
+    pub player: Player,
+    pub controls: InputController,
+    /// where the camera looks as (yaw, pitch), in degrees
+    pub look: (f32, f32),
+}
+
+impl Simulation {
+    /// applies the input of a tick to the controls and the view. Returns the released actions
+    /// the game has to take care of itself, like toggling the flashlight or starting over, but
+    /// only the ones the player can still do.
+    pub fn apply(&mut self, input: &TickInput) -> Vec<Action> {
+        if input.focus_lost {
+            self.controls = InputController::default();
+        }
+
+        let dead = self.player.is_dead();
+        let mut actions = Vec::new();
+        for &(action, pressed) in &input.actions {
+            let for_the_game = match action {
+                Action::ToggleFlashlight => !dead,
+                Action::Restart | Action::NewGame => dead,
+                _ => false,
+            };
+
+            if for_the_game && !pressed {
+                actions.push(action);
+            } else {
+                self.controls.apply(action, pressed);
+            }
+        }
+
+        // the dead don't do anything anymore, not even what they started before
+        if dead {
+            self.controls = InputController::default();
+        } else {
+            self.look.0 += input.look.0;
+            self.look.1 = (self.look.1 + input.look.1)
+                .max(-MouseLook::MAX_PITCH)
+                .min(MouseLook::MAX_PITCH);
+        }
+
+        actions
+    }
+
+    /// updates the player and walks them over the grid from `position`, as (x, z). Returns
+    /// where they end up.
+    pub fn step(
+        &mut self,
+        grid: &impl CollisionGrid,
+        position: (f32, f32),
+        on_ground: bool,
+        oxygen: &OxygenOptions,
+        dt: f32,
+    ) -> (f32, f32) {
+        self.player
+            .update(self.controls.intent(), on_ground, dt, oxygen);
+
+        let speed = self.player.state().speed();
+        walk(grid, &self.controls, self.look.0, speed, position, dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::level_generator::Level;
+    use crate::player::PlayerState;
+
+    fn oxygen() -> OxygenOptions {
+        OxygenOptions {
+            depletion_rate: 0.0,
+            run_multiplier: 1.0,
+            tank_refill: 0.35,
+            tanks: (0, 0),
+            tank_prop: String::new(),
+        }
+    }
+
+    fn tick(actions: &[(Action, bool)], look: (f32, f32)) -> TickInput {
+        TickInput {
+            actions: actions.to_vec(),
+            look,
+            focus_lost: false,
+        }
+    }
+
+    #[test]
+    fn game_actions_are_handed_back_on_release() {
+        let mut simulation = Simulation::default();
+
+        let pressed = tick(&[(Action::ToggleFlashlight, true)], (0.0, 0.0));
+        assert!(simulation.apply(&pressed).is_empty());
+        let released = tick(&[(Action::ToggleFlashlight, false)], (0.0, 0.0));
+        assert_eq!(simulation.apply(&released), vec![Action::ToggleFlashlight]);
+
+        // starting over is only for the dead
+        let restart = tick(&[(Action::Restart, false)], (0.0, 0.0));
+        assert!(simulation.apply(&restart).is_empty());
+    }
+
+    #[test]
+    fn the_dead_can_only_start_over() {
+        let level = Level::parse(
+            "#####
+             #...#
+             #####",
+        );
+        let mut simulation = Simulation::default();
+        simulation.apply(&tick(&[(Action::MoveRight, true)], (0.0, 0.0)));
+        simulation.player.damage(1.0, "testing");
+        simulation.step(&level, (2.0, 1.0), true, &oxygen(), 1.0 / 60.0);
+        assert_eq!(simulation.player.state(), PlayerState::Dead);
+
+        let input = tick(
+            &[
+                (Action::ToggleFlashlight, false),
+                (Action::MoveForward, true),
+                (Action::NewGame, false),
+            ],
+            (10.0, 10.0),
+        );
+        assert_eq!(simulation.apply(&input), vec![Action::NewGame]);
+        assert!(!simulation.controls.move_forward && !simulation.controls.move_right);
+        assert_eq!(simulation.look, (0.0, 0.0));
+    }
+
+    #[test]
+    fn losing_focus_lets_go_of_everything() {
+        let mut simulation = Simulation::default();
+        simulation.apply(&tick(&[(Action::MoveForward, true)], (0.0, 0.0)));
+        assert!(simulation.controls.move_forward);
+
+        simulation.apply(&TickInput {
+            focus_lost: true,
+            ..TickInput::default()
+        });
+        assert!(!simulation.controls.move_forward);
+    }
+
+    #[test]
+    fn pitch_stops_at_the_limit() {
+        let mut simulation = Simulation::default();
+        simulation.apply(&tick(&[], (30.0, 200.0)));
+        assert_eq!(simulation.look, (30.0, MouseLook::MAX_PITCH));
+    }
+}
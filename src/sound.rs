@@ -1,16 +1,14 @@
 use rand::{thread_rng, Rng};
+use rg3d::core::pool::Handle;
 use rg3d::engine::resource_manager::{ResourceManager, SharedSoundBuffer};
-use rg3d::sound::context::Context;
 use rg3d::sound::source::generic::GenericSourceBuilder;
 use rg3d::sound::source::spatial::SpatialSourceBuilder;
-use rg3d::sound::source::Status;
+use rg3d::sound::source::{SoundSource, Status};
 
-use crate::player::WalkState;
-use crate::player::WalkState::Running;
+use crate::audio::{AudioManager, Bus};
 use rg3d::physics::na::Vector3;
-use std::sync::{Arc, Mutex};
 
-pub async fn start_ambient_sound(ctx: Arc<Mutex<Context>>, resource_manager: ResourceManager) {
+pub async fn start_ambient_sound(audio: &mut AudioManager, resource_manager: ResourceManager) {
     let humming_buffer = resource_manager
         .request_sound_buffer("assets/humming.ogg", true)
         .await
@@ -24,28 +22,22 @@ pub async fn start_ambient_sound(ctx: Arc<Mutex<Context>>, resource_manager: Res
         .build_source()
         .unwrap();
 
-    // Each sound sound must be added to context, context takes ownership on source
-    // and returns pool handle to it by which it can be accessed later on if needed.
-    let _ = ctx.lock().unwrap().add_source(source);
+    audio.play_faded(source, Bus::Ambient, 3.0);
 }
 
-pub async fn add_air_vent_sound(
-    ctx: Arc<Mutex<Context>>,
-    resource_manager: &ResourceManager,
+pub fn add_prop_sound(
+    audio: &mut AudioManager,
+    buffer: SharedSoundBuffer,
+    gain: f32,
     pos_x: f32,
     pos_y: f32,
-) {
-    let air_vent = resource_manager
-        .request_sound_buffer("assets/air_vent.ogg", false)
-        .await
-        .unwrap();
-
-    let _ = ctx.lock().unwrap().add_source(
+) -> Handle<SoundSource> {
+    audio.play(
         SpatialSourceBuilder::new(
-            GenericSourceBuilder::new(air_vent.into())
+            GenericSourceBuilder::new(buffer.into())
                 .with_looping(true)
                 .with_status(Status::Playing)
-                .with_gain(0.5)
+                .with_gain(gain)
                 .build()
                 .unwrap(),
         )
@@ -54,25 +46,84 @@ pub async fn add_air_vent_sound(
         .with_max_distance(10.0)
         .with_rolloff_factor(2.5)
         .build_source(),
-    );
+        Bus::Ambient,
+    )
+}
+
+/// a looping sound filling a whole room, e.g. machines humming in engineering.
+pub fn add_room_ambience(
+    audio: &mut AudioManager,
+    ambience: SharedSoundBuffer,
+    gain: f32,
+    pos_x: f32,
+    pos_y: f32,
+    radius: f32,
+) -> Handle<SoundSource> {
+    audio.play_faded(
+        SpatialSourceBuilder::new(
+            GenericSourceBuilder::new(ambience.into())
+                .with_looping(true)
+                .with_status(Status::Playing)
+                .with_gain(gain)
+                .build()
+                .unwrap(),
+        )
+        .with_position(Vector3::new(pos_x, 1.0, pos_y))
+        .with_radius(radius)
+        .with_max_distance(radius * 2.0)
+        .build_source(),
+        Bus::Ambient,
+        1.0,
+    )
+}
+
+/// starts the looping heavy breathing of an exhausted player. It stays silent until
+/// `update_breathing` turns it up.
+pub async fn start_breathing_sound(
+    audio: &mut AudioManager,
+    resource_manager: ResourceManager,
+) -> Handle<SoundSource> {
+    let breathing_buffer = resource_manager
+        .request_sound_buffer("assets/breathing.wav", false)
+        .await
+        .unwrap();
+
+    let source = GenericSourceBuilder::new(breathing_buffer.into())
+        .with_status(Status::Playing)
+        .with_looping(true)
+        .with_gain(0.0)
+        .build_source()
+        .unwrap();
+
+    audio.play(source, Bus::Voice)
+}
+
+/// fades the breathing in while `exhausted` and out again afterwards.
+pub fn update_breathing(audio: &mut AudioManager, breathing: Handle<SoundSource>, exhausted: bool) {
+    const MAX_GAIN: f32 = 0.25;
+    /// seconds from silent to `MAX_GAIN`
+    const FADE_TIME: f32 = 1.25;
+
+    let target = if exhausted { MAX_GAIN } else { 0.0 };
+    audio.fade_to(breathing, target, FADE_TIME);
 }
 
-pub async fn load_footstep_sounds(resource_manager: &mut ResourceManager) -> SharedSoundBuffer {
+pub async fn load_hurt_sound(resource_manager: &mut ResourceManager) -> SharedSoundBuffer {
     resource_manager
-        .request_sound_buffer("assets/footstep.ogg", false)
+        .request_sound_buffer("assets/hurt.wav", false)
         .await
         .unwrap()
 }
 
-pub fn play_footstep(ctx: &mut Context, foot_step: SharedSoundBuffer, walk_state: &WalkState) {
-    let gain = if *walk_state == Running { 0.15 } else { 0.07 };
-    ctx.add_source(
-        GenericSourceBuilder::new(foot_step.into())
-            .with_play_once(true)
-            .with_gain(gain)
-            .with_pitch(thread_rng().gen_range(0.85, 1.0))
+/// a thud and a groan, louder for harder hits. `strength` is `Player::hurt`.
+pub fn play_hurt(audio: &mut AudioManager, hurt: SharedSoundBuffer, strength: f32) {
+    audio.play(
+        GenericSourceBuilder::new(hurt.into())
+            .with_gain(0.2 + 0.3 * strength)
+            .with_pitch(thread_rng().gen_range(0.9, 1.1))
             .with_status(Status::Playing)
             .build_source()
             .unwrap(),
+        Bus::Voice,
     );
 }
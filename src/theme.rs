@@ -0,0 +1,59 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::reverb::ReverbParameters;
+use crate::ron_file::load_ron;
+
+/// how a room is dressed. Themes are defined in `assets/themes.ron`.
+#[derive(Deserialize)]
+pub struct Theme {
+    pub name: String,
+    pub floor: String,
+    pub wall: String,
+    pub light_color: (u8, u8, u8),
+    #[serde(default)]
+    pub ambient_sound: Option<String>,
+    #[serde(default = "default_ambient_gain")]
+    pub ambient_gain: f32,
+    /// echo of a room of this theme with `ReverbOptions::reference_size` tiles
+    #[serde(default = "default_reverb")]
+    pub reverb: ReverbParameters,
+    /// names of the props that are placed in rooms of this theme
+    #[serde(default)]
+    pub props: Vec<String>,
+    /// relative chance of this theme being picked for a room
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_ambient_gain() -> f32 {
+    0.3
+}
+
+fn default_reverb() -> ReverbParameters {
+    ReverbParameters::DEFAULT
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+pub fn load_themes(path: &str) -> Vec<Theme> {
+    let themes: Vec<Theme> = load_ron(path);
+
+    if themes.is_empty() {
+        panic!("{} doesn't contain a single theme!", path);
+    }
+
+    themes
+}
+
+/// picks a theme index for every room, weighted by `Theme::weight`.
+pub fn assign_themes(themes: &[Theme], room_count: usize, rng: &mut StdRng) -> Vec<usize> {
+    let indices = (0..themes.len()).collect::<Vec<_>>();
+
+    (0..room_count)
+        .map(|_| *indices.choose_weighted(rng, |&i| themes[i].weight).unwrap())
+        .collect()
+}
@@ -0,0 +1,264 @@
+use std::collections::HashSet;
+use std::iter;
+
+use crate::collision::CollisionGrid;
+use crate::level_generator::Level;
+
+/// transforms the coordinates of the first octant into the other seven, see `cast_light`
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+/// whether there is a straight line between the centers of both tiles that doesn't cross a
+/// solid tile. The tiles themselves may be solid, so walls can be seen. Symmetric: if `a` sees
+/// `b`, `b` sees `a` as well.
+pub fn can_see(grid: &impl CollisionGrid, a: (i32, i32), b: (i32, i32)) -> bool {
+    let is_clear = |from, to| {
+        let between = tiles_between(from, to);
+        let line = iter::once(from)
+            .chain(between.iter().cloned())
+            .chain(iter::once(to))
+            .collect::<Vec<_>>();
+
+        between.iter().all(|&tile| !grid.is_solid(tile))
+            && line
+                .windows(2)
+                .all(|step| !squeezes_through_corner(grid, step[0], step[1]))
+    };
+    is_clear(a, b) || is_clear(b, a)
+}
+
+/// whether the diagonal step from `a` to `b` passes between two solid tiles touching at their
+/// corners. Their walls meet there, so there is no gap to see through.
+pub fn squeezes_through_corner(grid: &impl CollisionGrid, a: (i32, i32), b: (i32, i32)) -> bool {
+    a.0 != b.0 && a.1 != b.1 && grid.is_solid((b.0, a.1)) && grid.is_solid((a.0, b.1))
+}
+
+/// the tiles a straight line from the center of `from` to the center of `to` passes through, in
+/// order and without the tiles at both ends. Going back the other way may take different tiles.
+pub fn tiles_between(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = from;
+
+    // bresenham
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let mut tiles = Vec::new();
+    loop {
+        if (x, y) == to {
+            return tiles;
+        }
+
+        if (x, y) != from {
+            tiles.push((x, y));
+        }
+
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+impl Level {
+    /// all tiles that can be seen from `origin` within `radius` tiles, including the walls
+    /// bordering the visible area. Uses recursive shadowcasting.
+    pub fn visible_tiles(&self, origin: (usize, usize), radius: usize) -> HashSet<(usize, usize)> {
+        let mut visible = HashSet::new();
+        visible.insert(origin);
+
+        for &octant in OCTANTS.iter() {
+            self.cast_light(
+                (origin.0 as i32, origin.1 as i32),
+                radius as i32,
+                1,
+                1.0,
+                0.0,
+                octant,
+                &mut visible,
+            );
+        }
+
+        visible
+    }
+
+    /// scans one octant row by row, starting at `row`. `start` and `end` are the slopes that
+    /// are still lit; every blocking tile splits the lit area and continues recursively.
+    #[allow(clippy::too_many_arguments)]
+    fn cast_light(
+        &self,
+        origin: (i32, i32),
+        radius: i32,
+        row: i32,
+        mut start: f32,
+        end: f32,
+        (xx, xy, yx, yy): (i32, i32, i32, i32),
+        visible: &mut HashSet<(usize, usize)>,
+    ) {
+        if start < end {
+            return;
+        }
+
+        let mut new_start = 0.0;
+
+        for distance in row..=radius {
+            let dy = -distance;
+            let mut blocked = false;
+
+            for dx in -distance..=0 {
+                let x = origin.0 + dx * xx + dy * xy;
+                let y = origin.1 + dx * yx + dy * yy;
+
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
+                }
+
+                if dx * dx + dy * dy <= radius * radius && x >= 0 && y >= 0 {
+                    let tile = (x as usize, y as usize);
+                    if tile.0 < self.width() && tile.1 < self.height() {
+                        visible.insert(tile);
+                    }
+                }
+
+                let blocks_sight = self.is_solid((x, y));
+
+                if blocked {
+                    if blocks_sight {
+                        new_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if blocks_sight && distance < radius {
+                    blocked = true;
+                    self.cast_light(
+                        origin,
+                        radius,
+                        distance + 1,
+                        start,
+                        left_slope,
+                        (xx, xy, yx, yy),
+                        visible,
+                    );
+                    new_start = right_slope;
+                }
+            }
+
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::tests::{two_rooms, ClosedDoor};
+
+    #[test]
+    fn pillar_casts_a_shadow() {
+        let level = Level::parse(
+            "#########
+             #.......#
+             #.......#
+             #...#...#
+             #.......#
+             #.......#
+             #########",
+        );
+
+        assert!(!can_see(&level, (4, 1), (4, 5)));
+        assert!(can_see(&level, (1, 1), (1, 5)));
+
+        let visible = level.visible_tiles((4, 1), 10);
+        assert!(visible.contains(&(4, 3)), "the pillar itself can be seen");
+        assert!(!visible.contains(&(4, 4)));
+        assert!(!visible.contains(&(4, 5)));
+        assert!(visible.contains(&(1, 5)));
+        assert!(visible.contains(&(7, 5)));
+    }
+
+    #[test]
+    fn open_door_lets_sight_through_but_not_the_wall() {
+        let level = two_rooms();
+
+        assert!(can_see(&level, (3, 1), (3, 5)));
+        assert!(!can_see(&level, (1, 1), (1, 5)));
+
+        let visible = level.visible_tiles((3, 1), 10);
+        assert!(visible.contains(&(3, 5)));
+        assert!(!visible.contains(&(1, 5)));
+        assert!(!visible.contains(&(5, 5)));
+    }
+
+    #[test]
+    fn closed_door_blocks_sight() {
+        let grid = ClosedDoor {
+            level: two_rooms(),
+            door: (3, 3),
+        };
+
+        assert!(!can_see(&grid, (3, 1), (3, 5)));
+        assert!(
+            can_see(&grid, (3, 1), (3, 3)),
+            "the door itself can be seen"
+        );
+    }
+
+    #[test]
+    fn no_sight_through_diagonal_gaps() {
+        let level = Level::parse(
+            "######
+             #..#.#
+             #.#..#
+             #....#
+             ######",
+        );
+
+        assert!(!can_see(&level, (2, 1), (3, 2)));
+        assert!(!can_see(&level, (1, 0), (4, 3)));
+        assert!(can_see(&level, (2, 1), (1, 3)));
+    }
+
+    #[test]
+    fn sight_is_symmetric() {
+        let level = Level::parse(
+            "#########
+             #.......#
+             #..#....#
+             #.....#.#
+             #.#.....#
+             #.......#
+             #########",
+        );
+
+        for a in 0..level.width() as i32 * level.height() as i32 {
+            for b in 0..level.width() as i32 * level.height() as i32 {
+                let a = (a % level.width() as i32, a / level.width() as i32);
+                let b = (b % level.width() as i32, b / level.width() as i32);
+                assert_eq!(can_see(&level, a, b), can_see(&level, b, a));
+            }
+        }
+    }
+}
@@ -0,0 +1,566 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rg3d::core::math::ray::Ray;
+use rg3d::physics::geometry::{ColliderHandle, InteractionGroups};
+use rg3d::physics::na::Vector3;
+use rg3d::scene::light::Light;
+use rg3d::scene::node::Node;
+use rg3d::scene::physics::RayCastOptions;
+use rg3d::scene::Scene;
+
+use crate::audio::AudioManager;
+use crate::collision::CollisionGrid;
+use crate::interaction::{Action, Interactable, Outcome};
+use crate::level_config::LevelConfig;
+use crate::level_generator::{FieldType, Level};
+use crate::lighting::shadow_casters;
+use crate::reverb::ReverbParameters;
+use crate::scene_builder::{add_door_collider, build_level, LevelContent, SceneAssets};
+
+/// width and height of a chunk in tiles. Must be odd, see `Level::init_map`.
+pub const CHUNK_SIZE: usize = 31;
+
+/// chunks within this distance (in chunks) around the camera are kept in the scene.
+const LOAD_RADIUS: i32 = 1;
+
+/// chunks are only unloaded when they are this far away, so walking back and forth over a chunk
+/// border doesn't rebuild the same chunk over and over again.
+const UNLOAD_RADIUS: i32 = LOAD_RADIUS + 1;
+
+/// how far the player can see when handing out the shadows of the lights, in tiles
+const SHADOW_SIGHT_RADIUS: usize = 12;
+
+pub type ChunkPos = (i32, i32);
+
+/// an interactable in one of the loaded chunks. Only valid until the world changes.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Target {
+    pub chunk: ChunkPos,
+    /// index into the interactables of the chunk
+    pub index: usize,
+}
+
+pub struct Chunk {
+    pub level: Level,
+    content: LevelContent,
+    /// tiles of the doors that are closed, so collision doesn't have to look through all
+    /// interactables
+    closed_doors: HashSet<(usize, usize)>,
+}
+
+/// an endless station made of chunks. Every chunk is generated from the world seed and its
+/// position only, so a chunk looks exactly the same when it's loaded again. Neighbouring chunks
+/// agree on where the corridors cross their shared border.
+pub struct World {
+    seed: u64,
+    config: LevelConfig,
+    assets: SceneAssets,
+    chunks: HashMap<ChunkPos, Chunk>,
+    /// chunk and id of every item that was picked up, so they don't come back when the chunk is
+    /// loaded again
+    taken_pickups: HashSet<(ChunkPos, usize)>,
+    /// world positions of the tanks dropped by the player, they are placed again when their
+    /// chunk is loaded
+    dropped_tanks: Vec<(ChunkPos, Vector3<f32>)>,
+    /// chunk and tile of every door the player left open, doors are closed when they're built
+    opened_doors: HashSet<(ChunkPos, (usize, usize))>,
+    /// tile the shadows were handed out for last, `None` when chunks were loaded or unloaded
+    /// since
+    shadows_tile: Option<(i32, i32)>,
+}
+
+enum Border {
+    /// the border between `(x, y)` and `(x + 1, y)`
+    Vertical,
+    /// the border between `(x, y)` and `(x, y + 1)`
+    Horizontal,
+}
+
+impl World {
+    pub fn new(seed: u64, config: LevelConfig, assets: SceneAssets) -> Self {
+        Self {
+            seed,
+            config,
+            assets,
+            chunks: HashMap::new(),
+            taken_pickups: HashSet::new(),
+            dropped_tanks: Vec::new(),
+            opened_doors: HashSet::new(),
+            shadows_tile: None,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn config(&self) -> &LevelConfig {
+        &self.config
+    }
+
+    /// the chunk containing the given world position.
+    pub fn chunk_pos(position: Vector3<f32>) -> ChunkPos {
+        let tile_x = position.x.round() as i32;
+        let tile_y = position.z.round() as i32;
+
+        (
+            tile_x.div_euclid(CHUNK_SIZE as i32),
+            tile_y.div_euclid(CHUNK_SIZE as i32),
+        )
+    }
+
+    /// world position of the tile `(0, 0)` of a chunk.
+    pub fn chunk_origin(pos: ChunkPos) -> Vector3<f32> {
+        Vector3::new(
+            (pos.0 * CHUNK_SIZE as i32) as f32,
+            0.0,
+            (pos.1 * CHUNK_SIZE as i32) as f32,
+        )
+    }
+
+    /// a position on the floor of a room in the chunk at `(0, 0)`. Loads the chunk if needed.
+    pub fn spawn_point(&mut self, scene: &mut Scene, audio: &mut AudioManager) -> Vector3<f32> {
+        self.load_chunk(scene, audio, (0, 0));
+
+        let level = &self.chunks[&(0, 0)].level;
+
+        let (x, y) = level
+            .rooms
+            .first()
+            .map(|room| room[room.len() / 2])
+            .or_else(|| {
+                (0..level.width())
+                    .flat_map(|x| (0..level.height()).map(move |y| (x, y)))
+                    .find(|&(x, y)| level.map[x][y].typ != FieldType::Empty)
+            })
+            .expect("chunk without a single walkable tile!");
+
+        Vector3::new(x as f32, 0.0, y as f32)
+    }
+
+    /// loads all chunks around `position` and unloads the ones that are far away.
+    pub fn update(&mut self, scene: &mut Scene, audio: &mut AudioManager, position: Vector3<f32>) {
+        let center = World::chunk_pos(position);
+
+        let far_away = self
+            .chunks
+            .keys()
+            .filter(|&&(x, y)| {
+                (x - center.0).abs() > UNLOAD_RADIUS || (y - center.1).abs() > UNLOAD_RADIUS
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for pos in far_away {
+            if let Some(chunk) = self.chunks.remove(&pos) {
+                chunk.content.remove(scene, audio);
+                self.shadows_tile = None;
+            }
+        }
+
+        for x in (center.0 - LOAD_RADIUS)..=(center.0 + LOAD_RADIUS) {
+            for y in (center.1 - LOAD_RADIUS)..=(center.1 + LOAD_RADIUS) {
+                self.load_chunk(scene, audio, (x, y));
+            }
+        }
+    }
+
+    /// what kind of tile the position is on, `None` in chunks that aren't loaded.
+    pub fn field_type(&self, position: Vector3<f32>) -> Option<FieldType> {
+        let size = CHUNK_SIZE as i32;
+        let (x, y) = (position.x.round() as i32, position.z.round() as i32);
+        let pos = (x.div_euclid(size), y.div_euclid(size));
+        let tile = (x.rem_euclid(size) as usize, y.rem_euclid(size) as usize);
+
+        self.chunks
+            .get(&pos)
+            .map(|chunk| chunk.level.map[tile.0][tile.1].typ)
+    }
+
+    /// the echo at the position: the one of the room or of the corridors. Doors and chunks that
+    /// aren't loaded have none.
+    pub fn reverb(&self, position: Vector3<f32>) -> Option<ReverbParameters> {
+        let size = CHUNK_SIZE as i32;
+        let (x, y) = (position.x.round() as i32, position.z.round() as i32);
+        let chunk = self.chunks.get(&(x.div_euclid(size), y.div_euclid(size)))?;
+        let tile = (x.rem_euclid(size) as usize, y.rem_euclid(size) as usize);
+
+        match chunk.level.map[tile.0][tile.1].typ {
+            FieldType::Corridor => Some(self.config.reverb.corridor),
+            FieldType::Floor => chunk
+                .level
+                .rooms
+                .iter()
+                .position(|room| room.contains(&tile))
+                .map(|room| chunk.content.room_reverbs[room]),
+            FieldType::Door | FieldType::Empty => None,
+        }
+    }
+
+    /// unloads all chunks, e.g. before the scene is thrown away to restart the game.
+    pub fn clear(&mut self, scene: &mut Scene, audio: &mut AudioManager) {
+        for (_, chunk) in self.chunks.drain() {
+            chunk.content.remove(scene, audio);
+        }
+        self.shadows_tile = None;
+    }
+
+    /// animates the broken lights of all loaded chunks and hands out the shadows: lights that
+    /// can be seen from `position` get them first, then the closest ones. The shadows only change
+    /// when the player enters another tile or the loaded chunks change.
+    pub fn update_lights(&mut self, scene: &mut Scene, position: Vector3<f32>, dt: f32) {
+        for chunk in self.chunks.values_mut() {
+            for light in &mut chunk.content.flickering_lights {
+                light.update(scene, dt);
+            }
+        }
+
+        let tile = (position.x.round() as i32, position.z.round() as i32);
+        if self.shadows_tile == Some(tile) {
+            return;
+        }
+        self.shadows_tile = Some(tile);
+
+        // only the chunk the player is in, lights in the others are far enough away
+        let pos = World::chunk_pos(position);
+        let origin = World::chunk_origin(pos);
+        let visible = self.chunks.get(&pos).map_or_else(HashSet::new, |chunk| {
+            let tile = (
+                (position.x - origin.x).round() as usize,
+                (position.z - origin.z).round() as usize,
+            );
+            chunk.level.visible_tiles(tile, SHADOW_SIGHT_RADIUS)
+        });
+
+        let lights = self
+            .chunks
+            .values()
+            .flat_map(|chunk| chunk.content.lights.iter().cloned())
+            .collect::<Vec<_>>();
+        let seen = lights
+            .iter()
+            .map(|&light| {
+                let light_position = scene.graph[light].global_position();
+                let tile = (
+                    (light_position.x - origin.x).round() as usize,
+                    (light_position.z - origin.z).round() as usize,
+                );
+                let visible = World::chunk_pos(light_position) == pos && visible.contains(&tile);
+                (visible, (light_position - position).norm())
+            })
+            .collect::<Vec<_>>();
+
+        let casts = shadow_casters(&seen, self.config.lighting.max_shadow_casters);
+        for (&light, cast) in lights.iter().zip(casts) {
+            if let Node::Light(Light::Point(point)) = &mut scene.graph[light] {
+                point.set_cast_shadows(cast);
+            }
+        }
+    }
+
+    /// the interactable the view ray from `origin` in (normalized) `direction` points at, if
+    /// it's within reach and not behind a wall. `ignore` is the collider of the player.
+    pub fn find_target(
+        &self,
+        scene: &mut Scene,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        ignore: ColliderHandle,
+    ) -> Option<Target> {
+        let mut intersections = Vec::new();
+        scene.physics.cast_ray(
+            RayCastOptions {
+                ray: Ray::new(origin, direction),
+                max_len: Interactable::REACH,
+                groups: InteractionGroups::all(),
+                sort_results: true,
+            },
+            &mut intersections,
+        );
+
+        let wall_distance = intersections
+            .iter()
+            .find(|i| i.collider != ignore)
+            .map_or(Interactable::REACH, |i| i.toi);
+
+        let mut best: Option<(Target, f32)> = None;
+        for (&chunk, content) in self.chunks.iter().map(|(pos, chunk)| (pos, &chunk.content)) {
+            for (index, interactable) in content.interactables.iter().enumerate() {
+                let distance = match interactable.hit_distance(origin, direction) {
+                    // closed doors are hit by the ray themselves, so allow a little slack
+                    Some(distance) if distance <= wall_distance + 0.15 => distance,
+                    _ => continue,
+                };
+
+                if best.map_or(true, |(_, best_distance)| distance < best_distance) {
+                    best = Some((Target { chunk, index }, distance));
+                }
+            }
+        }
+
+        best.map(|(target, _)| target)
+    }
+
+    pub fn interactable(&self, target: Target) -> Option<&Interactable> {
+        self.chunks
+            .get(&target.chunk)
+            .and_then(|chunk| chunk.content.interactables.get(target.index))
+    }
+
+    /// uses the target. `player_position` keeps doors from closing on the player.
+    pub fn interact(
+        &mut self,
+        scene: &mut Scene,
+        target: Target,
+        player_position: Vector3<f32>,
+    ) -> Outcome {
+        let chunk = match self.chunks.get_mut(&target.chunk) {
+            Some(chunk) => chunk,
+            None => return Outcome::Nothing,
+        };
+        let content = &mut chunk.content;
+        if target.index >= content.interactables.len() {
+            return Outcome::Nothing;
+        }
+
+        let node = content.interactables[target.index].node;
+        let position = content.interactables[target.index].position;
+
+        match &mut content.interactables[target.index].action {
+            &mut Action::Pickup { item, id } => {
+                match id {
+                    Some(id) => {
+                        self.taken_pickups.insert((target.chunk, id));
+                    }
+                    None => {
+                        let mut forgotten = false;
+                        self.dropped_tanks.retain(|&(chunk, dropped)| {
+                            let same = !forgotten
+                                && chunk == target.chunk
+                                && (dropped.x - position.x).hypot(dropped.z - position.z) < 0.01;
+                            forgotten |= same;
+                            !same
+                        });
+                    }
+                }
+                scene.remove_node(node);
+                content.interactables.remove(target.index);
+                Outcome::PickedUp(item)
+            }
+            Action::Door(door) => {
+                match door.collider.take() {
+                    Some(collider) => {
+                        scene
+                            .physics
+                            .colliders
+                            .remove(collider, &mut scene.physics.bodies, true);
+                    }
+                    None => {
+                        let origin = World::chunk_origin(target.chunk);
+                        let tile = (
+                            origin.x as i32 + door.tile.0 as i32,
+                            origin.z as i32 + door.tile.1 as i32,
+                        );
+                        let player_tile = (
+                            player_position.x.round() as i32,
+                            player_position.z.round() as i32,
+                        );
+                        if tile == player_tile {
+                            return Outcome::Nothing;
+                        }
+
+                        door.collider =
+                            Some(add_door_collider(scene, content.body, door.tile, door.size));
+                    }
+                }
+                scene.graph[node].set_visibility(!door.is_open());
+
+                if door.is_open() {
+                    chunk.closed_doors.remove(&door.tile);
+                    self.opened_doors.insert((target.chunk, door.tile));
+                } else {
+                    chunk.closed_doors.insert(door.tile);
+                    self.opened_doors.remove(&(target.chunk, door.tile));
+                }
+                Outcome::Nothing
+            }
+            Action::Terminal { message } => Outcome::Message(message.clone()),
+            Action::LightSwitch { lights, on } => {
+                *on = !*on;
+                for &light in lights.iter() {
+                    scene.graph[light].set_visibility(*on);
+                }
+                for flickering in &mut content.flickering_lights {
+                    if lights.contains(&flickering.handle()) {
+                        flickering.set_enabled(scene, *on);
+                    }
+                }
+                Outcome::Nothing
+            }
+        }
+    }
+
+    fn load_chunk(&mut self, scene: &mut Scene, audio: &mut AudioManager, pos: ChunkPos) {
+        if self.chunks.contains_key(&pos) {
+            return;
+        }
+
+        self.shadows_tile = None;
+        let mut rng = StdRng::seed_from_u64(self.chunk_seed(pos));
+
+        let mut level = Level::create_dungeon(
+            CHUNK_SIZE,
+            CHUNK_SIZE,
+            self.config.rooms,
+            FieldType::Floor,
+            &self.exits(pos),
+            &mut rng,
+        );
+
+        let mut content = build_level(
+            &mut level,
+            scene,
+            audio,
+            &self.assets,
+            &self.config,
+            World::chunk_origin(pos),
+            &mut rng,
+        );
+
+        let taken_pickups = &self.taken_pickups;
+        content.interactables.retain(|interactable| {
+            let taken = match interactable.action {
+                Action::Pickup { id: Some(id), .. } => taken_pickups.contains(&(pos, id)),
+                _ => false,
+            };
+            if taken {
+                scene.remove_node(interactable.node);
+            }
+            !taken
+        });
+
+        for &(chunk, position) in &self.dropped_tanks {
+            if chunk == pos {
+                content
+                    .interactables
+                    .push(self.assets.add_dropped_oxygen_tank(
+                        scene,
+                        &self.config.oxygen,
+                        content.root,
+                        World::chunk_origin(pos),
+                        position,
+                    ));
+            }
+        }
+
+        let mut closed_doors = HashSet::new();
+        for interactable in &mut content.interactables {
+            if let Action::Door(door) = &mut interactable.action {
+                if !self.opened_doors.contains(&(pos, door.tile)) {
+                    closed_doors.insert(door.tile);
+                } else if let Some(collider) = door.collider.take() {
+                    scene
+                        .physics
+                        .colliders
+                        .remove(collider, &mut scene.physics.bodies, true);
+                    scene.graph[interactable.node].set_visibility(false);
+                }
+            }
+        }
+
+        self.chunks.insert(
+            pos,
+            Chunk {
+                level,
+                content,
+                closed_doors,
+            },
+        );
+    }
+
+    /// puts an oxygen tank on the floor at `position`. Returns false if it can't be placed
+    /// there because its chunk isn't loaded.
+    pub fn drop_oxygen_tank(&mut self, scene: &mut Scene, position: Vector3<f32>) -> bool {
+        let pos = World::chunk_pos(position);
+        let chunk = match self.chunks.get_mut(&pos) {
+            Some(chunk) => chunk,
+            None => return false,
+        };
+
+        let position = Vector3::new(position.x, 0.0, position.z);
+        chunk
+            .content
+            .interactables
+            .push(self.assets.add_dropped_oxygen_tank(
+                scene,
+                &self.config.oxygen,
+                chunk.content.root,
+                World::chunk_origin(pos),
+                position,
+            ));
+        self.dropped_tanks.push((pos, position));
+        true
+    }
+
+    /// the border tiles of a chunk that lead into its four neighbours.
+    fn exits(&self, (x, y): ChunkPos) -> Vec<(usize, usize)> {
+        let last = CHUNK_SIZE - 1;
+
+        vec![
+            (0, self.border_crossing((x - 1, y), Border::Vertical)),
+            (last, self.border_crossing((x, y), Border::Vertical)),
+            (self.border_crossing((x, y - 1), Border::Horizontal), 0),
+            (self.border_crossing((x, y), Border::Horizontal), last),
+        ]
+    }
+
+    /// the odd tile along a chunk border where a corridor crosses it. Both chunks sharing the
+    /// border compute the same value, that's why the walls line up.
+    fn border_crossing(&self, pos: ChunkPos, border: Border) -> usize {
+        let salt = match border {
+            Border::Vertical => 0x5652_5449_4341_4c00,
+            Border::Horizontal => 0x484f_5249_5a4f_4e00,
+        };
+
+        let hash = mix(self.seed ^ salt ^ mix(pos.0 as u64) ^ mix(mix(pos.1 as u64)));
+
+        // odd positions between 1 and CHUNK_SIZE - 2
+        let crossings = (CHUNK_SIZE - 1) / 2;
+        (hash % crossings as u64) as usize * 2 + 1
+    }
+
+    fn chunk_seed(&self, pos: ChunkPos) -> u64 {
+        mix(self.seed ^ mix(pos.0 as u64) ^ mix(mix(pos.1 as u64)).rotate_left(32))
+    }
+}
+
+impl CollisionGrid for World {
+    /// tiles in chunks that aren't loaded are solid, nobody should be able to walk there anyway.
+    /// So are closed doors.
+    fn is_solid(&self, (x, y): (i32, i32)) -> bool {
+        let size = CHUNK_SIZE as i32;
+        let pos = (x.div_euclid(size), y.div_euclid(size));
+        let tile = (x.rem_euclid(size), y.rem_euclid(size));
+
+        match self.chunks.get(&pos) {
+            Some(chunk) => {
+                chunk.level.is_solid(tile)
+                    || chunk
+                        .closed_doors
+                        .contains(&(tile.0 as usize, tile.1 as usize))
+            }
+            None => true,
+        }
+    }
+}
+
+/// splitmix64 finalizer. Turns similar inputs (like neighbouring chunk positions) into
+/// completely different seeds.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}